@@ -1,8 +1,15 @@
 mod isa;
-use crate::cpu::isa::{Instruction, AddrMode, InstructionType};
+mod prng;
+#[cfg(test)]
+mod single_step;
+pub use crate::cpu::isa::{Instruction, AddrMode, InstructionType};
+pub use crate::cpu::prng::Prng;
 use crate::util;
+use std::collections::VecDeque;
 use std::fmt;
+use std::io;
 use std::num::Wrapping;
+use std::ops::RangeInclusive;
 
 // Status Register bit descriptions
 //
@@ -21,9 +28,50 @@ const CARRY_BIT: u8 = 0;
 const ZERO_BIT: u8 = 1;
 const INT_DISABLE_BIT: u8 = 2;
 const DECIMAL_BIT: u8 = 3;
+const BREAK_BIT: u8 = 4;
+const UNUSED_BIT: u8 = 5;
 const OVERFLOW_BIT: u8 = 6;
 const NEGATIVE_BIT: u8 = 7;
 
+// named status register flags for use outside of the CPU module
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatusFlag {
+    Carry,
+    Zero,
+    InterruptDisable,
+    Decimal,
+    Overflow,
+    Negative,
+}
+impl StatusFlag {
+    fn bit(self) -> u8 {
+        match self {
+            StatusFlag::Carry => CARRY_BIT,
+            StatusFlag::Zero => ZERO_BIT,
+            StatusFlag::InterruptDisable => INT_DISABLE_BIT,
+            StatusFlag::Decimal => DECIMAL_BIT,
+            StatusFlag::Overflow => OVERFLOW_BIT,
+            StatusFlag::Negative => NEGATIVE_BIT,
+        }
+    }
+}
+
+// interrupt vector locations
+pub const NMI_VECTOR: u16 = 0xfffa;
+pub const RESET_VECTOR: u16 = 0xfffc;
+pub const IRQ_VECTOR: u16 = 0xfffe;
+
+// easy6502 stores the ASCII code of the last pressed key at $ff
+pub const LAST_KEY_ADDR: u16 = 0x00ff;
+
+// processor model, the NES uses an NMOS 6502
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Variant {
+    Nmos6502,
+    // enables the (zp) addressing mode and BRA
+    Wdc65C02,
+}
+
 
 trait BitOps {
     // common bit operations
@@ -64,7 +112,107 @@ impl BitOps for u8 {
 }
 
 
+/*** memory access logging ***/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+// single memory access performed by the CPU
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AccessRecord {
+    pub kind: AccessKind,
+    pub addr: u16,
+    pub value: u8,
+    pub cycle: u64,     // cycle of the access when cycle stepping, otherwise the start of the instruction
+}
+
+
+/*** cycle stepping ***/
+// instruction part way through being executed one cycle at a time by tick_cycle
+#[derive(Debug, PartialEq)]
+struct CycleStep {
+    instruction: Instruction,
+    // address of the opcode
+    pc: u16,
+    // cycles of the instruction completed so far
+    done: u8,
+}
+
+
+/*** instruction stepping iterator ***/
+// yields the address of every executed instruction, stops after an error or a halt
+pub struct Instructions<'a> {
+    cpu: &'a mut CPU,
+    done: bool,
+}
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Result<u16, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.cpu.halted {
+            return None;
+        }
+        let pc = self.cpu.pc;
+        match self.cpu.tick() {
+            Ok(()) => Some(Ok(pc)),
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+
+/*** memory reader ***/
+// streams bytes out of CPU memory with std::io, starting at an address and
+// advancing without side effects, reading stops at the top of memory
+pub struct MemoryReader<'a> {
+    cpu: &'a CPU,
+    addr: usize,
+}
+impl<'a> io::Read for MemoryReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let end = self.cpu.ram.len().min(self.addr + buf.len());
+        let count = end - self.addr;
+        buf[..count].copy_from_slice(&self.cpu.ram[self.addr..end]);
+        self.addr = end;
+        Ok(count)
+    }
+}
+
+
+/*** instruction inspection ***/
+// decoded view of the instruction at PC for debugger UIs
+#[derive(Debug, PartialEq)]
+pub struct InstructionInfo {
+    pub mnemonic: &'static str,
+    pub addr_mode: AddrMode,
+    // bytes following the opcode
+    pub operand_bytes: Vec<u8>,
+    // memory address the instruction reads, writes or jumps to
+    pub effective_address: Option<u16>,
+    // current byte at the effective address, None for branches
+    pub value: Option<u8>,
+    // clock cycles the instruction takes in the current state
+    pub cycles: u8,
+}
+
+
 /*** CPU structure ***/
+// register file of the CPU, used to construct a CPU in a custom state
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CpuRegisters {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub sp: u8,
+    pub pc: u16,
+    pub sr: u8,
+}
+
 #[derive(Debug)]
 pub struct CPU {
     // addressable memory space
@@ -77,12 +225,57 @@ pub struct CPU {
     pub sp: u8,
     pub pc: u16,
     pub sr: u8,
+
+    // clock cycles elapsed since init
+    pub cycles: u64,
+
+    // cycles left before the instruction started by tick_cycle completes
+    remaining_cycles: u64,
+    cycle_step: Option<CycleStep>,
+
+    // instruction set understood by the decoder
+    pub variant: Variant,
+
+    // opt-in detection of jumps and branches to themselves, which never make progress
+    pub halt_on_self_jump: bool,
+    pub halted: bool,
+
+    // opt-in software breakpoints: BRK halts at its own address instead of interrupting
+    pub break_on_brk: bool,
+
+    // opt-in address ranges PC may execute from, anything else is reported as an error
+    execution_guard: Option<Vec<RangeInclusive<u16>>>,
+
+    // instruction trace, optionally limited to a PC range and captured instead of printed
+    trace_filter: Option<RangeInclusive<u16>>,
+    trace_capture: Option<Vec<String>>,
+
+    // interrupt inputs: NMI is edge triggered and latched, IRQ is level triggered
+    nmi_pending: bool,
+    pub irq_line: bool,
+
+    // optional random number generator overriding reads of its address
+    prng: Option<Prng>,
+
+    // rolling log of recent memory accesses, disabled when None
+    access_log: Option<VecDeque<AccessRecord>>,
+    access_log_capacity: usize,
+
+    // opt-in backtrace of the (pc, opcode) pairs of recently fetched instructions
+    instruction_history: Option<VecDeque<(u16, u8)>>,
+    instruction_history_capacity: usize,
+
+    // opt-in record of every access made by the last executed instruction
+    pub record_accesses: bool,
+    recorded_accesses: Vec<(AccessKind, u16, u8)>,
 }
 impl CPU {
     pub fn init() -> Self {
         // enable interrupt_disable bit on startup
+        // the unused bit is always 1 on the 6502
         let mut init_sr = 0;
         init_sr.set_bit(INT_DISABLE_BIT);
+        init_sr.set_bit(UNUSED_BIT);
 
         CPU {
             // zero out CPU memory
@@ -95,21 +288,583 @@ impl CPU {
             sp: 0u8,
             pc: 0u16,
             sr: init_sr,
+
+            cycles: 0,
+            remaining_cycles: 0,
+            cycle_step: None,
+
+            variant: Variant::Nmos6502,
+
+            halt_on_self_jump: false,
+            halted: false,
+
+            break_on_brk: false,
+
+            execution_guard: None,
+
+            trace_filter: None,
+            trace_capture: None,
+
+            nmi_pending: false,
+            irq_line: false,
+
+            prng: None,
+
+            access_log: None,
+            access_log_capacity: 0,
+
+            instruction_history: None,
+            instruction_history_capacity: 0,
+
+            record_accesses: false,
+            recorded_accesses: Vec::new(),
         }
     }
 
     // forward emulation by one clock cycle
     pub fn tick(&mut self) -> Result<(), String> {
+        // finish an instruction left part way through by tick_cycle
+        if self.cycle_step.is_some() {
+            while !self.tick_cycle()? {}
+            return Ok(());
+        }
+        // the cycles tick_cycle still had to wait are already counted
+        self.remaining_cycles = 0;
+
+        let instruction = self.fetch_decode()?;
+        self.run_instruction(&instruction)
+    }
+
+    // fetch and decode the instruction at PC
+    fn fetch_decode(&mut self) -> Result<Instruction, String> {
+        if let Some(regions) = &self.execution_guard {
+            if !regions.iter().any(|region| region.contains(&self.pc)) {
+                return Err(format!("Execution from non-executable address ${:04x}", self.pc));
+            }
+        }
+
+        self.recorded_accesses.clear();
+
         // Fetch
-        let next_index = self.pc as usize;
-        let instruction_bytes = &self.ram[next_index..next_index+3];
+        let instruction_bytes = self.fetch_instruction_bytes();
+        if let Some(history) = &mut self.instruction_history {
+            if self.instruction_history_capacity > 0 {
+                if history.len() == self.instruction_history_capacity {
+                    history.pop_front();
+                }
+                history.push_back((self.pc, instruction_bytes[0]));
+            }
+        }
 
         // Decode
-        let instruction = Instruction::from(instruction_bytes)?;
+        self.decode(&instruction_bytes)
+    }
+
+    // execute a fetched instruction as a whole and service pending interrupts
+    fn run_instruction(&mut self, instruction: &Instruction) -> Result<(), String> {
+        if self.break_on_brk && instruction.ins_type == InstructionType::BRK {
+            self.halted = true;
+            return Ok(());
+        }
+        for (offset, byte) in instruction.machine_code.iter().enumerate() {
+            self.log_access(AccessKind::Read, self.pc.wrapping_add(offset as u16), *byte);
+        }
 
         // Execute
-        println!("${:04x}: {}{}  // {}", self.pc, instruction, self, instruction.name.description);
-        self.execute(&instruction);
+        self.trace(instruction);
+        let crossed_page = self.crosses_page(instruction);
+        let branch_taken = self.branch_taken(instruction);
+        let instruction_pc = self.pc;
+        if let Err(e) = self.execute(instruction) {
+            // leave PC at the failing instruction
+            self.pc = instruction_pc;
+            return Err(e);
+        }
+        self.cycles += CPU::cycles_for(instruction, crossed_page, branch_taken) as u64;
+
+        self.poll_interrupts();
+        // an interrupt serviced after the instruction leaves the idle loop
+        if self.halt_on_self_jump && self.pc == instruction_pc {
+            self.halted = true;
+        }
+        Ok(())
+    }
+
+    // read a little endian word without side effects, wrapping at $ffff
+    pub fn peek_u16(&self, addr: u16) -> u16 {
+        let low_byte = self.ram[addr as usize];
+        let high_byte = self.ram[addr.wrapping_add(1) as usize];
+        (high_byte as u16) << 8 | (low_byte as u16)
+    }
+
+    // little endian operand word of the instruction at pc, without side effects
+    // the bytes following the opcode wrap around from $ffff to $0000
+    pub fn fetch_u16(&self, pc: u16) -> u16 {
+        self.peek_u16(pc.wrapping_add(1))
+    }
+
+    // handler addresses stored in the interrupt vectors
+    pub fn nmi_addr(&self) -> u16 {
+        self.peek_u16(NMI_VECTOR)
+    }
+    pub fn reset_addr(&self) -> u16 {
+        self.peek_u16(RESET_VECTOR)
+    }
+    pub fn irq_addr(&self) -> u16 {
+        self.peek_u16(IRQ_VECTOR)
+    }
+
+    // signal a non-maskable interrupt, serviced after the current instruction
+    pub fn trigger_nmi(&mut self) {
+        self.nmi_pending = true;
+    }
+
+    // service at most one pending interrupt, NMI takes priority over IRQ
+    fn poll_interrupts(&mut self) {
+        if self.nmi_pending {
+            self.nmi_pending = false;
+            self.service_interrupt(NMI_VECTOR);
+        } else if self.irq_line && self.sr.get_bit(INT_DISABLE_BIT) == 0 {
+            self.service_interrupt(IRQ_VECTOR);
+        }
+    }
+
+    // push return address and status, then jump through the interrupt vector
+    fn service_interrupt(&mut self, vector: u16) {
+        let mut status = self.sr;
+        status.clear_bit(BREAK_BIT);
+        status.set_bit(UNUSED_BIT);
+
+        self.stack_push(self.pc);
+        self.stack_push_byte(status);
+        self.sr.set_bit(INT_DISABLE_BIT);
+        self.pc = self.read_u16(vector);
+        self.cycles += 7;
+    }
+
+    // forward emulation by a single clock cycle, returns true on the cycle that
+    // completes the instruction
+    // immediate, zero page and absolute reads and stores fetch their bytes and
+    // access memory on the cycles the 6502 does, other instructions execute as
+    // a whole on their first cycle and the remaining cycles only wait
+    pub fn tick_cycle(&mut self) -> Result<bool, String> {
+        // a halted CPU stays put, cycles pass without completing anything
+        if self.halted {
+            return Ok(false);
+        }
+        if self.remaining_cycles > 0 {
+            self.remaining_cycles -= 1;
+            return Ok(self.remaining_cycles == 0);
+        }
+        if let Some(step) = self.cycle_step.take() {
+            return self.continue_cycle_step(step);
+        }
+
+        let instruction = self.fetch_decode()?;
+        if !CPU::steps_cycles(&instruction) {
+            let start = self.cycles;
+            self.run_instruction(&instruction)?;
+            // e.g. break_on_brk halts before the instruction takes any cycles
+            if self.cycles == start {
+                return Ok(false);
+            }
+            self.remaining_cycles = self.cycles - start - 1;
+            return Ok(self.remaining_cycles == 0);
+        }
+
+        // opcode fetch
+        self.trace(&instruction);
+        self.log_access(AccessKind::Read, self.pc, instruction.machine_code[0]);
+        let step = CycleStep { instruction, pc: self.pc, done: 1 };
+        self.pc = self.pc.wrapping_add(1);
+        self.cycles += 1;
+        self.cycle_step = Some(step);
+        Ok(false)
+    }
+
+    // instructions tick_cycle executes cycle by cycle
+    fn steps_cycles(instruction: &Instruction) -> bool {
+        let reads_or_stores = matches!(instruction.ins_type,
+            InstructionType::LDA | InstructionType::LDX | InstructionType::LDY |
+            InstructionType::ADC | InstructionType::SBC | InstructionType::AND |
+            InstructionType::BIT | InstructionType::CMP | InstructionType::CPX |
+            InstructionType::CPY | InstructionType::STA | InstructionType::STX |
+            InstructionType::STY);
+        reads_or_stores && matches!(instruction.addr_mode, AddrMode::Imm(_) | AddrMode::Zpg(_) | AddrMode::Abs(_))
+    }
+
+    // run the next cycle of an instruction started by tick_cycle
+    //   Imm: opcode, operand
+    //   Zpg: opcode, address, memory access
+    //   Abs: opcode, address low, address high, memory access
+    fn continue_cycle_step(&mut self, mut step: CycleStep) -> Result<bool, String> {
+        let length = step.instruction.machine_code.len() as u8;
+        let last = match step.instruction.addr_mode {
+            AddrMode::Imm(_) => step.done == 1,
+            _ => step.done == length,
+        };
+        if !last {
+            // operand fetch
+            let byte = step.instruction.machine_code[step.done as usize];
+            self.log_access(AccessKind::Read, self.pc, byte);
+            self.pc = self.pc.wrapping_add(1);
+            self.cycles += 1;
+            step.done += 1;
+            self.cycle_step = Some(step);
+            return Ok(false);
+        }
+
+        if let AddrMode::Imm(value) = step.instruction.addr_mode {
+            self.log_access(AccessKind::Read, self.pc, value);
+        }
+        // execute advances PC past the instruction on its own
+        self.pc = step.pc;
+        if let Err(e) = self.execute(&step.instruction) {
+            // leave PC at the failing instruction and undo its cycles
+            self.cycles -= step.done as u64;
+            return Err(e);
+        }
+        self.cycles += 1;
+
+        // an interrupt serviced now delays completion until its sequence ends
+        let start = self.cycles;
+        self.poll_interrupts();
+        self.remaining_cycles = self.cycles - start;
+        Ok(self.remaining_cycles == 0)
+    }
+
+    // run exactly the given number of clock cycles, e.g. to drive the CPU
+    // from an external clock, an instruction may be left partially complete
+    pub fn advance(&mut self, cycles: u64) -> Result<(), String> {
+        for _i in 0..cycles {
+            self.tick_cycle()?;
+        }
+        Ok(())
+    }
+
+    // restrict execution to the given address ranges, None allows executing anywhere
+    // e.g. CPU RAM and cartridge space: vec![0x0000..=0x1fff, 0x4020..=0xffff]
+    pub fn set_execution_guard(&mut self, regions: Option<Vec<RangeInclusive<u16>>>) {
+        self.execution_guard = regions;
+    }
+
+    // only trace instructions whose PC falls in the range, None traces everything
+    pub fn set_trace_filter(&mut self, filter: Option<RangeInclusive<u16>>) {
+        self.trace_filter = filter;
+    }
+
+    // collect trace lines in a buffer instead of printing them to stdout
+    pub fn capture_trace(&mut self) {
+        self.trace_capture = Some(Vec::new());
+    }
+
+    // trace lines collected since capture_trace() was called
+    pub fn captured_trace(&self) -> &[String] {
+        match &self.trace_capture {
+            Some(lines) => lines,
+            None => &[],
+        }
+    }
+
+    // std::io::Read over memory starting at addr, e.g. for dumping buffers from RAM
+    pub fn memory_reader(&self, addr: u16) -> MemoryReader<'_> {
+        MemoryReader { cpu: self, addr: addr as usize }
+    }
+
+    // execute a JSR together with the whole subroutine it calls, any other
+    // instruction is executed as a single step
+    pub fn step_over(&mut self) -> Result<(), String> {
+        let instruction = self.decode(&self.fetch_instruction_bytes())?;
+        if instruction.ins_type != InstructionType::JSR {
+            return self.tick();
+        }
+
+        // recursive calls pass through the return address with a deeper stack
+        let return_pc = self.pc.wrapping_add(instruction.machine_code.len() as u16);
+        let sp = self.sp;
+        self.tick()?;
+        while (self.pc != return_pc || self.sp != sp) && !self.halted {
+            self.tick()?;
+        }
+        Ok(())
+    }
+
+    // run until the current subroutine returns to its caller, which is the
+    // first RTS executed with the stack pointer at or above where it was when
+    // stepping out began, values pushed before that are pulled again by then
+    pub fn step_out(&mut self) -> Result<(), String> {
+        let sp = self.sp;
+        while !self.halted {
+            let instruction = self.decode(&self.fetch_instruction_bytes())?;
+            let returns = instruction.ins_type == InstructionType::RTS && self.sp >= sp;
+            self.tick()?;
+            if returns {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    // step through instructions with an iterator, e.g. `cpu.instructions().take(100)`
+    pub fn instructions(&mut self) -> Instructions<'_> {
+        Instructions { cpu: self, done: false }
+    }
+
+    // number of clock cycles an instruction takes to execute
+    //   crossed_page: the indexed address or the branch target is on a different page
+    //   branch_taken: the branch condition was met (ignored for non-branch instructions)
+    pub fn cycles_for(instruction: &Instruction, crossed_page: bool, branch_taken: bool) -> u8 {
+        match instruction.ins_type {
+            // branches take an extra cycle when taken and another when crossing a page
+            InstructionType::BCC | InstructionType::BCS | InstructionType::BEQ |
+            InstructionType::BMI | InstructionType::BNE | InstructionType::BPL |
+            InstructionType::BVC | InstructionType::BVS | InstructionType::BRA => {
+                2 + branch_taken as u8 + (branch_taken && crossed_page) as u8
+            }
+
+            // control flow and stack
+            InstructionType::BRK => 7,
+            InstructionType::JSR | InstructionType::RTI | InstructionType::RTS => 6,
+            InstructionType::JMP => match instruction.addr_mode {
+                AddrMode::Ind(_) => 5,
+                _ => 3,
+            },
+            InstructionType::PHA | InstructionType::PHP => 3,
+            InstructionType::PLA | InstructionType::PLP => 4,
+
+            // stores always spend the page crossing cycle
+            InstructionType::STA | InstructionType::STX | InstructionType::STY => {
+                match instruction.addr_mode {
+                    AddrMode::Zpg(_) => 3,
+                    AddrMode::ZpgX(_) | AddrMode::ZpgY(_) | AddrMode::Abs(_) => 4,
+                    AddrMode::AbsX(_) | AddrMode::AbsY(_) | AddrMode::ZpgInd(_) => 5,
+                    _ => 6,
+                }
+            }
+
+            // read-modify-write
+            InstructionType::ASL | InstructionType::LSR | InstructionType::ROL |
+            InstructionType::ROR | InstructionType::INC | InstructionType::DEC => {
+                match instruction.addr_mode {
+                    AddrMode::Zpg(_) => 5,
+                    AddrMode::ZpgX(_) | AddrMode::Abs(_) => 6,
+                    AddrMode::AbsX(_) => 7,
+                    _ => 2,
+                }
+            }
+
+            // reads take an extra cycle when indexing crosses a page
+            _ => match instruction.addr_mode {
+                AddrMode::Zpg(_) => 3,
+                AddrMode::ZpgX(_) | AddrMode::ZpgY(_) | AddrMode::Abs(_) => 4,
+                AddrMode::AbsX(_) | AddrMode::AbsY(_) => 4 + crossed_page as u8,
+                AddrMode::XInd(_) => 6,
+                AddrMode::IndY(_) => 5 + crossed_page as u8,
+                AddrMode::ZpgInd(_) => 5,
+                _ => 2,
+            },
+        }
+    }
+
+    // disassemble the instruction at PC without executing it
+    // branch targets and memory operands are shown resolved, e.g. "; $0604"
+    pub fn disassemble_current(&self) -> Result<String, String> {
+        let instruction = self.decode(&self.fetch_instruction_bytes())?;
+        let disassembly = format!("${:04x}: {}", self.pc, instruction);
+        match self.effective_address(&instruction) {
+            Some(addr) => Ok(format!("{}; ${:04x}", disassembly, addr)),
+            None => Ok(disassembly.trim_end().to_string()),
+        }
+    }
+
+    // decode the instruction at PC and resolve its operand without side effects
+    pub fn inspect_current(&self) -> Result<InstructionInfo, String> {
+        let instruction = self.decode(&self.fetch_instruction_bytes())?;
+        let effective_address = self.effective_address(&instruction);
+        let value = match instruction.addr_mode {
+            AddrMode::Rel(_) => None,
+            _ => effective_address.map(|addr| self.ram[addr as usize]),
+        };
+        let cycles = CPU::cycles_for(
+            &instruction, self.crosses_page(&instruction), self.branch_taken(&instruction)
+        );
+
+        Ok(InstructionInfo {
+            mnemonic: instruction.name.mnemonic,
+            operand_bytes: instruction.machine_code[1..].to_vec(),
+            addr_mode: instruction.addr_mode,
+            effective_address,
+            value,
+            cycles,
+        })
+    }
+
+    // CPU with a custom initial status register instead of the power-on
+    // state with interrupts disabled, bits 4 and 5 are fixed up as in PLP
+    pub fn with_status(status: u8) -> Self {
+        let mut cpu = CPU::init();
+        cpu.set_sr_from_stack(status);
+        cpu
+    }
+
+    // CPU with the given register state and zeroed memory, the status
+    // register is fixed up like in with_status
+    pub fn with_state(registers: CpuRegisters) -> Self {
+        let mut cpu = CPU::init();
+        cpu.a = registers.a;
+        cpu.x = registers.x;
+        cpu.y = registers.y;
+        cpu.sp = registers.sp;
+        cpu.pc = registers.pc;
+        cpu.set_sr_from_stack(registers.sr);
+        cpu
+    }
+
+    // current register state
+    pub fn registers(&self) -> CpuRegisters {
+        CpuRegisters {
+            a: self.a,
+            x: self.x,
+            y: self.y,
+            sp: self.sp,
+            pc: self.pc,
+            sr: self.sr,
+        }
+    }
+
+    // human readable snapshot for bug reports: registers, decoded flags, the top
+    // of the stack, recently executed instructions and the instruction at PC
+    pub fn dump_state(&self) -> String {
+        // set flags in uppercase, clear flags in lowercase
+        let flags: String = "NV-BDIZC".chars().enumerate()
+            .map(|(i, letter)| match self.sr.get_bit(7 - i as u8) {
+                1 => letter,
+                _ => letter.to_ascii_lowercase(),
+            })
+            .collect();
+
+        // at most 8 bytes above the stack pointer, the most recently pushed first
+        let stack_top = self.sp as usize + 1;
+        let stack: Vec<String> = (stack_top..=0xff).take(8)
+            .map(|offset| format!("{:02x}", self.ram[0x0100 + offset]))
+            .collect();
+
+        let recent: Vec<String> = self.recent_instructions().iter()
+            .map(|(pc, opcode)| format!("${:04x}:{:02x}", pc, opcode))
+            .collect();
+
+        let disassembly = match self.disassemble_current() {
+            Ok(disassembly) => disassembly,
+            Err(e) => format!("${:04x}: {}", self.pc, e),
+        };
+
+        format!("A:${:02x} X:${:02x} Y:${:02x} SP:${:02x} PC:${:04x}\n\
+                 SR:${:02x} {}\n\
+                 Stack: {}\n\
+                 Recent: {}\n\
+                 {}\n",
+            self.a, self.x, self.y, self.sp, self.pc, self.sr, flags, stack.join(" "), recent.join(" "), disassembly
+        )
+    }
+
+    // compare emulated machine state: registers, memory, cycle count,
+    // interrupt inputs and devices; debugging aids like the access log are ignored
+    pub fn state_equals(&self, other: &CPU) -> bool {
+        self.registers() == other.registers()
+            && self.ram == other.ram
+            && self.cycles == other.cycles
+            && self.remaining_cycles == other.remaining_cycles
+            && self.cycle_step == other.cycle_step
+            && self.nmi_pending == other.nmi_pending
+            && self.irq_line == other.irq_line
+            && self.halted == other.halted
+            && self.prng == other.prng
+    }
+
+    // read a single status register flag
+    pub fn get_flag(&self, flag: StatusFlag) -> bool {
+        self.sr.get_bit(flag.bit()) == 1
+    }
+
+    // set or clear a single status register flag
+    pub fn set_flag(&mut self, flag: StatusFlag, value: bool) {
+        self.sr.assign_bit(flag.bit(), value as u8);
+    }
+
+    // provide the memory conventions easy6502 programs rely on:
+    // random bytes at $fe and the last pressed key at $ff
+    pub fn enable_easy6502_io(&mut self, seed: u16) {
+        self.attach_prng(Prng::new(seed));
+        self.set_last_key(0);
+    }
+
+    // report a key press to the program the way easy6502 does
+    pub fn set_last_key(&mut self, key: u8) {
+        self.ram[LAST_KEY_ADDR as usize] = key;
+    }
+
+    // map a random number generator over its address, replacing any previous one
+    pub fn attach_prng(&mut self, prng: Prng) {
+        self.prng = Some(prng);
+    }
+
+    // start recording the last `capacity` memory accesses
+    pub fn enable_access_log(&mut self, capacity: usize) {
+        self.access_log = Some(VecDeque::with_capacity(capacity));
+        self.access_log_capacity = capacity;
+    }
+
+    // stop recording memory accesses and discard the log
+    pub fn disable_access_log(&mut self) {
+        self.access_log = None;
+    }
+
+    // recorded memory accesses from oldest to newest
+    pub fn access_log(&self) -> Vec<AccessRecord> {
+        match &self.access_log {
+            Some(log) => log.iter().copied().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // start remembering the last `capacity` fetched instructions
+    pub fn enable_instruction_history(&mut self, capacity: usize) {
+        self.instruction_history = Some(VecDeque::with_capacity(capacity));
+        self.instruction_history_capacity = capacity;
+    }
+
+    // (pc, opcode) of recently fetched instructions from oldest to newest,
+    // the last entry is the instruction that ran or failed most recently
+    pub fn recent_instructions(&self) -> Vec<(u16, u8)> {
+        match &self.instruction_history {
+            Some(history) => history.iter().copied().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // accesses made by the last executed instruction in order, including
+    // instruction fetches, recorded while `record_accesses` is set
+    pub fn recorded_accesses(&self) -> &[(AccessKind, u16, u8)] {
+        &self.recorded_accesses
+    }
+
+    // copy a block of bytes to memory at once, bypassing the access log and
+    // any mapped devices, the block must fit below the top of memory
+    pub fn load_bytes(&mut self, addr: u16, bytes: &[u8]) -> Result<(), String> {
+        let start = addr as usize;
+        let end = start + bytes.len();
+        if end > self.ram.len() {
+            return Err(format!("{} bytes at ${:04x} do not fit in memory", bytes.len(), addr));
+        }
+        self.ram[start..end].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    // load a raw program and start executing it from its first byte
+    // all of memory is writable RAM, so programs may also modify themselves
+    pub fn load_program(&mut self, bytes: &[u8], start: u16) -> Result<(), String> {
+        self.load_bytes(start, bytes)?;
+        self.pc = start;
         Ok(())
     }
 
@@ -139,9 +894,7 @@ impl CPU {
                 .collect::<Vec<u8>>();
 
             // copy bytes to memory
-            for b in bytes.iter().enumerate() {
-                self.ram[addr as usize + b.0] = *b.1;
-            }
+            self.load_bytes(addr, bytes)?;
         }
         println!();
 
@@ -149,30 +902,35 @@ impl CPU {
     }
 
     // execute single machine instruction
-    fn execute(&mut self, instruction: &Instruction) {
+    // PC already points to the next instruction while executing, like on the
+    // real CPU, so branches and jumps simply overwrite it
+    fn execute(&mut self, instruction: &Instruction) -> Result<(), String> {
+        // addition is wrapping since instructions may straddle $ffff
+        self.pc = self.pc.wrapping_add(instruction.machine_code.len() as u16);
+
         match instruction.ins_type {
 
             // Load Accumulator with Memory
             InstructionType::LDA => {
-                self.a = self.get_operand(instruction);
+                self.a = self.get_operand(instruction)?;
                 self.set_sr_nz(self.a);
             }
 
             // Load Index X with Memory
             InstructionType::LDX => {
-                self.x = self.get_operand(instruction);
+                self.x = self.get_operand(instruction)?;
                 self.set_sr_nz(self.x);
             }
 
             // Load Index Y with Memory
             InstructionType::LDY => {
-                self.y = self.get_operand(instruction);
+                self.y = self.get_operand(instruction)?;
                 self.set_sr_nz(self.y);
             }
 
             // Shift One Bit Right (Memory or Accumulator)
             InstructionType::LSR => {
-                let operand = self.get_operand(instruction);
+                let operand = self.get_operand(instruction)?;
                 let result = operand >> 1;
 
                 // rightmost bit gets assigned to carry
@@ -184,63 +942,162 @@ impl CPU {
                         self.a = result;
                     }
                     AddrMode::Zpg(addr) => {
-                        self.ram[*addr as usize] = result;
+                        self.write(*addr as u16, result);
                     }
                     AddrMode::ZpgX(addr) => {
-                        self.ram[*addr as usize + self.x as usize] = result;
+                        self.write(CPU::zero_page_indexed(*addr, self.x), result);
                     }
                     AddrMode::Abs(addr) => {
-                        self.ram[*addr as usize] = result;
+                        self.write(*addr, result);
                     }
                     AddrMode::AbsX(addr) => {
-                        self.ram[*addr as usize + self.x as usize] = result;
+                        self.write(CPU::absolute_indexed(*addr, self.x), result);
                     }
-                    _ => panic!("Illegal addressing mode for STA!")
+                    _ => return Err(format!("Illegal addressing mode for LSR: {:?}", instruction.addr_mode))
                 }
             }
 
-            // No Operation
-            InstructionType::NOP => {}
+            // Shift Left One Bit (Memory or Accumulator)
+            InstructionType::ASL => {
+                let operand = self.get_operand(instruction)?;
+                let result = operand << 1;
 
-            // Set Carry Flag
-            InstructionType::SEC => { self.sr.set_bit(CARRY_BIT); }
+                // leftmost bit gets assigned to carry
+                self.sr.assign_bit(CARRY_BIT, operand.get_bit(7));
+                self.set_sr_nz(result);
 
-            // Return from Subroutine
-            InstructionType::RTS => {
-                self.pc = self.stack_pop()+1;
-                self.pc -= instruction.machine_code.len() as u16; // compensate for normal pc adjustment
+                match &instruction.addr_mode {
+                    AddrMode::A => {
+                        self.a = result;
+                    }
+                    AddrMode::Zpg(addr) => {
+                        self.write(*addr as u16, result);
+                    }
+                    AddrMode::ZpgX(addr) => {
+                        self.write(CPU::zero_page_indexed(*addr, self.x), result);
+                    }
+                    AddrMode::Abs(addr) => {
+                        self.write(*addr, result);
+                    }
+                    AddrMode::AbsX(addr) => {
+                        self.write(CPU::absolute_indexed(*addr, self.x), result);
+                    }
+                    _ => return Err(format!("Illegal addressing mode for ASL: {:?}", instruction.addr_mode))
+                }
             }
 
-            // Subtract Memory from Accumulator with Borrow
-            InstructionType::SBC => {
-                let operand = !self.get_operand(instruction);
-                let carry_in = self.sr.get_bit(CARRY_BIT);
-
-                // set overflow flag if appropriate
-                let carry_in_added_i8 = (self.a as i8).overflowing_add(carry_in as i8);
-                let operand_added_i8 = carry_in_added_i8.0.overflowing_add(operand as i8);
-                let overflow: u8 = match carry_in_added_i8.1 | operand_added_i8.1 {
-                    false => 0u8,
-                    true => 1u8,
-                };
-
-                // compute sum and carry out flag
-                let carry_in_added = self.a.overflowing_add(carry_in);
-                let operand_added = carry_in_added.0.overflowing_add(operand);
-                let carry_out: u8 = match carry_in_added.1 | operand_added.1 {
-                    false => 0,
-                    true => 1,
-                };
+            // Rotate One Bit Left (Memory or Accumulator)
+            InstructionType::ROL => {
+                let operand = self.get_operand(instruction)?;
+                let result = (operand << 1) | self.sr.get_bit(CARRY_BIT);
 
-                self.a = operand_added.0;
-                self.sr.assign_bit(OVERFLOW_BIT, overflow);
-                self.sr.assign_bit(CARRY_BIT, carry_out);
-                self.set_sr_nz(self.a);
-            }
+                // leftmost bit gets assigned to carry, old carry to bit 0
+                self.sr.assign_bit(CARRY_BIT, operand.get_bit(7));
+                self.set_sr_nz(result);
+
+                match &instruction.addr_mode {
+                    AddrMode::A => {
+                        self.a = result;
+                    }
+                    AddrMode::Zpg(addr) => {
+                        self.write(*addr as u16, result);
+                    }
+                    AddrMode::ZpgX(addr) => {
+                        self.write(CPU::zero_page_indexed(*addr, self.x), result);
+                    }
+                    AddrMode::Abs(addr) => {
+                        self.write(*addr, result);
+                    }
+                    AddrMode::AbsX(addr) => {
+                        self.write(CPU::absolute_indexed(*addr, self.x), result);
+                    }
+                    _ => return Err(format!("Illegal addressing mode for ROL: {:?}", instruction.addr_mode))
+                }
+            }
+
+            // Rotate One Bit Right (Memory or Accumulator)
+            InstructionType::ROR => {
+                let operand = self.get_operand(instruction)?;
+                let result = (operand >> 1) | (self.sr.get_bit(CARRY_BIT) << 7);
+
+                // rightmost bit gets assigned to carry, old carry to bit 7
+                self.sr.assign_bit(CARRY_BIT, operand.get_bit(0));
+                self.set_sr_nz(result);
+
+                match &instruction.addr_mode {
+                    AddrMode::A => {
+                        self.a = result;
+                    }
+                    AddrMode::Zpg(addr) => {
+                        self.write(*addr as u16, result);
+                    }
+                    AddrMode::ZpgX(addr) => {
+                        self.write(CPU::zero_page_indexed(*addr, self.x), result);
+                    }
+                    AddrMode::Abs(addr) => {
+                        self.write(*addr, result);
+                    }
+                    AddrMode::AbsX(addr) => {
+                        self.write(CPU::absolute_indexed(*addr, self.x), result);
+                    }
+                    _ => return Err(format!("Illegal addressing mode for ROR: {:?}", instruction.addr_mode))
+                }
+            }
+
+            // No Operation
+            InstructionType::NOP => {}
+
+            // Set Carry Flag
+            InstructionType::SEC => { self.sr.set_bit(CARRY_BIT); }
+
+            // Push Accumulator on Stack
+            InstructionType::PHA => { self.stack_push_byte(self.a); }
+
+            // Pull Processor Status from Stack
+            InstructionType::PLP => {
+                let status = self.stack_pop_byte();
+                self.set_sr_from_stack(status);
+            }
+
+            // Return from Interrupt
+            InstructionType::RTI => {
+                let status = self.stack_pop_byte();
+                self.set_sr_from_stack(status);
+                self.pc = self.stack_pop();
+            }
+
+            // Return from Subroutine
+            InstructionType::RTS => {
+                // JSR pushes the address of its last byte
+                self.pc = self.stack_pop().wrapping_add(1);
+            }
+
+            // Subtract Memory from Accumulator with Borrow
+            InstructionType::SBC => {
+                let operand = self.get_operand(instruction)?;
+                let borrow_in = 1 - self.sr.get_bit(CARRY_BIT);
+
+                // compute difference, carry out is the inverse of borrow out
+                let difference = self.a as i16 - operand as i16 - borrow_in as i16;
+                let result = difference as u8;
+                let carry_out: u8 = match difference < 0 {
+                    false => 1,
+                    true => 0,
+                };
+
+                // overflow if the operands have different signs and the sign
+                // of the result differs from the sign of the minuend
+                let overflow = ((self.a ^ operand) & (self.a ^ result)).get_bit(7);
+
+                self.a = result;
+                self.sr.assign_bit(OVERFLOW_BIT, overflow);
+                self.sr.assign_bit(CARRY_BIT, carry_out);
+                self.set_sr_nz(self.a);
+            }
 
             // Add Memory to Accumulator with Carry
             InstructionType::ADC => {
-                let operand = self.get_operand(instruction);
+                let operand = self.get_operand(instruction)?;
                 let carry_in = self.sr.get_bit(CARRY_BIT);
 
                 // set overflow flag if appropriate
@@ -267,42 +1124,15 @@ impl CPU {
 
             // AND Memory with Accumulator
             InstructionType::AND => {
-                let operand = self.get_operand(instruction);
+                let operand = self.get_operand(instruction)?;
 
                 self.a &= operand;
                 self.set_sr_nz(self.a);
             }
 
-            // ASL  Shift Left One Bit (Memory or Accumulator)
-            // TODO: implement
-
-            // Branch on Carry Clear
-            InstructionType::BCC => {
-                let operand = self.get_operand(instruction);
-                if self.sr.get_bit(CARRY_BIT) == 0 {
-                    self.pc = self.pc.wrapping_add((operand as i8) as u16);
-                }
-            }
-
-            // Branch on Carry Clear
-            InstructionType::BCS => {
-                let operand = self.get_operand(instruction);
-                if self.sr.get_bit(CARRY_BIT) == 1 {
-                    self.pc = self.pc.wrapping_add((operand as i8) as u16);
-                }
-            }
-
-            // Branch on Result Zero
-            InstructionType::BEQ => {
-                let operand = self.get_operand(instruction);
-                if self.sr.get_bit(ZERO_BIT) == 1 {
-                    self.pc = self.pc.wrapping_add((operand as i8) as u16);
-                }
-            }
-
             // BIT  Test Bits in Memory with Accumulator
             InstructionType::BIT => {
-                let operand = self.get_operand(instruction);
+                let operand = self.get_operand(instruction)?;
                 self.sr.assign_bit(NEGATIVE_BIT, operand.get_bit(NEGATIVE_BIT));
                 self.sr.assign_bit(OVERFLOW_BIT, operand.get_bit(OVERFLOW_BIT));
                 match self.a & operand {
@@ -311,50 +1141,27 @@ impl CPU {
                 }
             }
 
-            // Branch on Result Minus
-            InstructionType::BMI => {
-                let operand = self.get_operand(instruction);
-                if self.sr.get_bit(NEGATIVE_BIT) == 1 {
-                    self.pc = self.pc.wrapping_add((operand as i8) as u16);
-                }
-            }
-
-            // Branch on Result not Zero
-            InstructionType::BNE => {
-                let operand = self.get_operand(instruction);
-                if self.sr.get_bit(ZERO_BIT) == 0 {
-                    self.pc = self.pc.wrapping_add((operand as i8) as u16);
-                }
-            }
-
-            // Branch on Result Plus
-            InstructionType::BPL => {
-                let operand = self.get_operand(instruction);
-                if self.sr.get_bit(NEGATIVE_BIT) == 0 {
-                    self.pc = self.pc.wrapping_add((operand as i8) as u16);
-                }
-            }
-
             // Force Break
             InstructionType::BRK => {
-                panic!("TODO: implement CPU interrupts");
-                self.stack_push(self.pc+2);
-                self.stack_push_byte(self.sr);
+                // the return address skips the padding byte following BRK and
+                // the pushed status has the break bit set, unlike for IRQ and NMI
+                let mut status = self.sr;
+                status.set_bit(BREAK_BIT);
+                status.set_bit(UNUSED_BIT);
+
+                self.stack_push(self.pc.wrapping_add(1));
+                self.stack_push_byte(status);
                 self.sr.set_bit(INT_DISABLE_BIT);
+                self.pc = self.read_u16(IRQ_VECTOR);
             }
 
-            // Branch on Overflow Clear
-            InstructionType::BVC => {
-                let operand = self.get_operand(instruction);
-                if self.sr.get_bit(OVERFLOW_BIT) == 0 {
-                    self.pc = self.pc.wrapping_add((operand as i8) as u16);
-                }
-            }
-
-            // Branch on Overflow Set
-            InstructionType::BVS => {
-                let operand = self.get_operand(instruction);
-                if self.sr.get_bit(OVERFLOW_BIT) == 1 {
+            // Branch on Carry Clear / Carry Set / Result Zero / Result Minus /
+            // Result not Zero / Result Plus / Overflow Clear / Overflow Set
+            InstructionType::BCC | InstructionType::BCS | InstructionType::BEQ |
+            InstructionType::BMI | InstructionType::BNE | InstructionType::BPL |
+            InstructionType::BVC | InstructionType::BVS | InstructionType::BRA => {
+                let operand = self.get_operand(instruction)?;
+                if self.branch_taken(instruction) {
                     self.pc = self.pc.wrapping_add((operand as i8) as u16);
                 }
             }
@@ -373,60 +1180,56 @@ impl CPU {
 
             // Compare Memory with Accumulator
             InstructionType::CMP => {
-                let operand = self.get_operand(instruction);
+                let operand = self.get_operand(instruction)?;
                 let result = self.a.overflowing_sub(operand).0;
-                if self.a >= operand {
-                    self.sr.set_bit(CARRY_BIT);
-                }
+                self.sr.assign_bit(CARRY_BIT, (self.a >= operand) as u8);
                 self.set_sr_nz(result)
             }
 
-            // Compare Memory with Accumulator
+            // Compare Memory and Index X
             InstructionType::CPX => {
-                let operand = self.get_operand(instruction);
+                let operand = self.get_operand(instruction)?;
                 let result = self.x.overflowing_sub(operand).0;
-                if self.x >= operand {
-                    self.sr.set_bit(CARRY_BIT);
-                }
+                self.sr.assign_bit(CARRY_BIT, (self.x >= operand) as u8);
                 self.set_sr_nz(result)
             }
 
-            // Compare Memory with Accumulator
+            // Compare Memory and Index Y
             InstructionType::CPY => {
-                let operand = self.get_operand(instruction);
+                let operand = self.get_operand(instruction)?;
                 let result = self.y.overflowing_sub(operand).0;
-                if self.y >= operand {
-                    self.sr.set_bit(CARRY_BIT);
-                }
+                self.sr.assign_bit(CARRY_BIT, (self.y >= operand) as u8);
                 self.set_sr_nz(result)
             }
 
             InstructionType::DEC => {
-                let operand = self.get_operand(instruction);
+                let operand = self.get_operand(instruction)?;
                 let result = operand.overflowing_sub(1).0;
                 self.set_sr_nz(result);
                 match &instruction.addr_mode {
                     AddrMode::Zpg(addr) => {
-                        self.ram[*addr as usize] = result;
+                        self.write(*addr as u16, result);
                     }
                     AddrMode::ZpgX(addr) => {
-                        self.ram[*addr as usize + self.x as usize] = result;
+                        self.write(CPU::zero_page_indexed(*addr, self.x), result);
                     }
                     AddrMode::Abs(addr) => {
-                        self.ram[*addr as usize] = result;
+                        self.write(*addr, result);
                     }
                     AddrMode::AbsX(addr) => {
-                        self.ram[*addr as usize + self.x as usize] = result;
+                        self.write(CPU::absolute_indexed(*addr, self.x), result);
                     }
-                    _ => panic!("Illegal addressing mode for DEC!")
+                    _ => return Err(format!("Illegal addressing mode for DEC: {:?}", instruction.addr_mode))
                 }
             }
 
+            // Decrement Index X by One
             InstructionType::DEX => {
                 self.x = self.x.overflowing_sub(1).0;
                 self.set_sr_nz(self.x);
             }
 
+            // Decrement Index Y by One
             InstructionType::DEY => {
                 self.y = self.y.overflowing_sub(1).0;
                 self.set_sr_nz(self.y);
@@ -434,23 +1237,23 @@ impl CPU {
 
             // Increment Memory by One
             InstructionType::INC => {
-                let operand = self.get_operand(instruction);
+                let operand = self.get_operand(instruction)?;
                 let result = operand.overflowing_add(1).0;
                 self.set_sr_nz(result);
                 match &instruction.addr_mode {
                     AddrMode::Zpg(addr) => {
-                        self.ram[*addr as usize] = result;
+                        self.write(*addr as u16, result);
                     }
                     AddrMode::ZpgX(addr) => {
-                        self.ram[*addr as usize + self.x as usize] = result;
+                        self.write(CPU::zero_page_indexed(*addr, self.x), result);
                     }
                     AddrMode::Abs(addr) => {
-                        self.ram[*addr as usize] = result;
+                        self.write(*addr, result);
                     }
                     AddrMode::AbsX(addr) => {
-                        self.ram[*addr as usize + self.x as usize] = result;
+                        self.write(CPU::absolute_indexed(*addr, self.x), result);
                     }
-                    _ => panic!("Illegal addressing mode for INC!")
+                    _ => return Err(format!("Illegal addressing mode for INC: {:?}", instruction.addr_mode))
                 }
             }
 
@@ -470,19 +1273,18 @@ impl CPU {
             InstructionType::JMP => {
                 let jump_addr = match &instruction.addr_mode {
                     AddrMode::Abs(addr) => *addr,
-                    AddrMode::Ind(addr) => { panic!("Indirect jump addressing not implemented!") }
-                    _ => panic!("Illegal addressing mode for JMP!")
+                    AddrMode::Ind(_) => return Err("Indirect jump addressing not implemented".to_string()),
+                    _ => return Err(format!("Illegal addressing mode for JMP: {:?}", instruction.addr_mode))
                 };
                 self.pc = jump_addr;
-                self.pc -= instruction.machine_code.len() as u16; // compensate for normal pc adjustment
             }
 
             // Jump to New Location Saving Return Address
             InstructionType::JSR => {
                 if let AddrMode::Abs(addr) = &instruction.addr_mode {
-                    self.stack_push(self.pc+2);
+                    // push the address of the last byte of JSR
+                    self.stack_push(self.pc.wrapping_sub(1));
                     self.pc = *addr;
-                    self.pc -= instruction.machine_code.len() as u16; // compensate for normal pc adjustment
                 }
             }
 
@@ -490,29 +1292,33 @@ impl CPU {
             InstructionType::STA => {
                 match &instruction.addr_mode {
                     AddrMode::Zpg(addr) => {
-                        self.ram[*addr as usize] = self.a;
+                        self.write(*addr as u16, self.a);
                     }
                     AddrMode::ZpgX(addr) => {
-                        self.ram[*addr as usize + self.x as usize] = self.a;
+                        self.write(CPU::zero_page_indexed(*addr, self.x), self.a);
                     }
                     AddrMode::Abs(addr) => {
-                        self.ram[*addr as usize] = self.a;
+                        self.write(*addr, self.a);
                     }
                     AddrMode::AbsX(addr) => {
-                        self.ram[*addr as usize + self.x as usize] = self.a;
+                        self.write(CPU::absolute_indexed(*addr, self.x), self.a);
                     }
                     AddrMode::AbsY(addr) => {
-                        self.ram[*addr as usize + self.y as usize] = self.a;
+                        self.write(CPU::absolute_indexed(*addr, self.y), self.a);
                     }
                     AddrMode::XInd(addr) => {
-                        let indirect = self.ram[(*addr + self.x) as usize] as usize;
-                        self.ram[indirect] = self.a
+                        let indirect = self.read_u16_zp(addr.wrapping_add(self.x));
+                        self.write(indirect, self.a);
                     }
                     AddrMode::IndY(addr) => {
-                        let indirect = self.ram[*addr as usize] as usize;
-                        self.ram[indirect + self.y as usize] = self.a
+                        let indirect = self.read_u16_zp(*addr);
+                        self.write(CPU::absolute_indexed(indirect, self.y), self.a);
+                    }
+                    AddrMode::ZpgInd(addr) => {
+                        let indirect = self.read_u16_zp(*addr);
+                        self.write(indirect, self.a);
                     }
-                    _ => panic!("Illegal addressing mode for STA!")
+                    _ => return Err(format!("Illegal addressing mode for STA: {:?}", instruction.addr_mode))
                 }
             }
 
@@ -520,15 +1326,15 @@ impl CPU {
             InstructionType::STX => {
                 match &instruction.addr_mode {
                     AddrMode::Zpg(addr) => {
-                        self.ram[*addr as usize] = self.x;
+                        self.write(*addr as u16, self.x);
                     }
                     AddrMode::ZpgY(addr) => {
-                        self.ram[*addr as usize + self.y as usize] = self.x;
+                        self.write(CPU::zero_page_indexed(*addr, self.y), self.x);
                     }
                     AddrMode::Abs(addr) => {
-                        self.ram[*addr as usize] = self.x;
+                        self.write(*addr, self.x);
                     }
-                    _ => panic!("Illegal addressing mode for STX!")
+                    _ => return Err(format!("Illegal addressing mode for STX: {:?}", instruction.addr_mode))
                 }
             }
 
@@ -536,15 +1342,15 @@ impl CPU {
             InstructionType::STY => {
                 match &instruction.addr_mode {
                     AddrMode::Zpg(addr) => {
-                        self.ram[*addr as usize] = self.y;
+                        self.write(*addr as u16, self.y);
                     }
                     AddrMode::ZpgX(addr) => {
-                        self.ram[*addr as usize + self.x as usize] = self.y;
+                        self.write(CPU::zero_page_indexed(*addr, self.x), self.y);
                     }
                     AddrMode::Abs(addr) => {
-                        self.ram[*addr as usize] = self.y;
+                        self.write(*addr, self.y);
                     }
-                    _ => panic!("Illegal addressing mode for STX!")
+                    _ => return Err(format!("Illegal addressing mode for STY: {:?}", instruction.addr_mode))
                 }
             }
 
@@ -581,22 +1387,70 @@ impl CPU {
                 self.set_sr_nz(self.a);
             }
 
-            _ => panic!("Emulation for the instruction not yet implemented!\n  {:?}", instruction)
+            _ => return Err(format!("Emulation for the instruction not yet implemented: {:?}", instruction))
         }
 
-        // addition is wrapping since some branch instructions rely on this behavior
-        self.pc = self.pc.wrapping_add(instruction.machine_code.len() as u16);
+        Ok(())
+    }
+
+    // memory access
+    fn read(&mut self, addr: u16) -> u8 {
+        let value = match &mut self.prng {
+            Some(prng) if prng.addr == addr => prng.next_byte(),
+            _ => self.ram[addr as usize],
+        };
+        self.log_access(AccessKind::Read, addr, value);
+        value
+    }
+    fn write(&mut self, addr: u16, value: u8) {
+        self.ram[addr as usize] = value;
+        self.log_access(AccessKind::Write, addr, value);
+    }
+    fn log_access(&mut self, kind: AccessKind, addr: u16, value: u8) {
+        if self.record_accesses {
+            self.recorded_accesses.push((kind, addr, value));
+        }
+        // a log with zero capacity records nothing
+        if let Some(log) = &mut self.access_log {
+            if self.access_log_capacity == 0 {
+                return;
+            }
+            if log.len() == self.access_log_capacity {
+                log.pop_front();
+            }
+            log.push_back(AccessRecord { kind, addr, value, cycle: self.cycles });
+        }
+    }
+
+    // read a little endian word
+    fn read_u16(&mut self, addr: u16) -> u16 {
+        let low_byte = self.read(addr);
+        let high_byte = self.read(addr.wrapping_add(1));
+        (high_byte as u16) << 8 | (low_byte as u16)
+    }
+    // read a little endian pointer from the zero page
+    // the high byte comes from the next zero page address, wrapping from $ff to $00
+    fn read_u16_zp(&mut self, addr: u8) -> u16 {
+        let low_byte = self.read(addr as u16);
+        let high_byte = self.read(addr.wrapping_add(1) as u16);
+        (high_byte as u16) << 8 | (low_byte as u16)
+    }
+    // same as read_u16_zp without logging the access
+    fn peek_u16_zp(&self, addr: u8) -> u16 {
+        let low_byte = self.ram[addr as usize];
+        let high_byte = self.ram[addr.wrapping_add(1) as usize];
+        (high_byte as u16) << 8 | (low_byte as u16)
     }
 
     // stack manipulation
     fn stack_push_byte(&mut self, byte: u8) {
-        self.ram[0x0100 as usize + self.sp as usize] = byte;
+        self.write(0x0100 + self.sp as u16, byte);
         self.sp = (Wrapping(self.sp) - Wrapping(1u8)).0;
     }
     // pop byte from stack
     fn stack_pop_byte(&mut self) -> u8 {
         self.sp = (Wrapping(self.sp) + Wrapping(1u8)).0;
-        self.ram[0x0100 as usize + self.sp as usize]
+        self.read(0x0100 + self.sp as u16)
     }
     // push u16 to stack (high byte first)
     fn stack_push(&mut self, value: u16) {
@@ -612,55 +1466,159 @@ impl CPU {
 
 
     /*** common functionality used to implement instruction emulation ***/
+    // emit the trace line of the instruction about to execute
+    fn trace(&mut self, instruction: &Instruction) {
+        if let Some(filter) = &self.trace_filter {
+            if !filter.contains(&self.pc) {
+                return;
+            }
+        }
+        let line = format!("${:04x}: {}{}  // {}", self.pc, instruction, self, instruction.name.description);
+        match &mut self.trace_capture {
+            Some(lines) => lines.push(line),
+            None => println!("{}", line),
+        }
+    }
+
+    // bytes of the instruction at PC (opcode followed by up to two operand bytes)
+    // operand bytes wrap around to $0000 past the top of memory
+    fn fetch_instruction_bytes(&self) -> [u8; 3] {
+        let operand = self.fetch_u16(self.pc);
+        [self.ram[self.pc as usize], operand as u8, (operand >> 8) as u8]
+    }
+
+    // decode an instruction with the opcodes of the selected processor variant
+    fn decode(&self, bytes: &[u8]) -> Result<Instruction, String> {
+        match self.variant {
+            Variant::Nmos6502 => Instruction::from(bytes),
+            Variant::Wdc65C02 => Instruction::from_65c02(bytes),
+        }
+    }
+
+    // indexed addresses shared by execution and the debugger views
+    // zero page indexing wraps within the zero page, e.g. $f0,X with X=$20 is $0010
+    fn zero_page_indexed(addr: u8, index: u8) -> u16 {
+        addr.wrapping_add(index) as u16
+    }
+    // absolute indexing wraps from $ffff to $0000
+    fn absolute_indexed(addr: u16, index: u8) -> u16 {
+        addr.wrapping_add(index as u16)
+    }
+
+    // resolve the memory address targeted by the instruction at PC
+    // returns None for addressing modes that do not reference memory
+    fn effective_address(&self, instruction: &Instruction) -> Option<u16> {
+        match &instruction.addr_mode {
+            AddrMode::A | AddrMode::Imm(_) | AddrMode::Impl => None,
+            AddrMode::Abs(addr) => Some(*addr),
+            AddrMode::AbsX(addr) => Some(CPU::absolute_indexed(*addr, self.x)),
+            AddrMode::AbsY(addr) => Some(CPU::absolute_indexed(*addr, self.y)),
+            AddrMode::Ind(addr) => Some(self.peek_u16(*addr)),
+            AddrMode::XInd(addr) => Some(self.peek_u16_zp(addr.wrapping_add(self.x))),
+            AddrMode::IndY(addr) => Some(CPU::absolute_indexed(self.peek_u16_zp(*addr), self.y)),
+            AddrMode::ZpgInd(addr) => Some(self.peek_u16_zp(*addr)),
+            AddrMode::Rel(offset) => {
+                // branch offsets are relative to the address of the next instruction
+                let next_pc = self.pc.wrapping_add(instruction.machine_code.len() as u16);
+                Some(next_pc.wrapping_add(*offset as u16))
+            }
+            AddrMode::Zpg(addr) => Some(*addr as u16),
+            AddrMode::ZpgX(addr) => Some(CPU::zero_page_indexed(*addr, self.x)),
+            AddrMode::ZpgY(addr) => Some(CPU::zero_page_indexed(*addr, self.y)),
+        }
+    }
+
     // get instruction operand according to the associated addressing mode
     // operand of relative addressing is also returned as u8
-    fn get_operand(&self, instruction: &Instruction) -> u8 {
+    fn get_operand(&mut self, instruction: &Instruction) -> Result<u8, String> {
         match &instruction.addr_mode {
             AddrMode::A => {
-                self.a
+                Ok(self.a)
             }
             AddrMode::Abs(addr) => {
-                self.ram[*addr as usize]
+                Ok(self.read(*addr))
             }
             AddrMode::AbsX(addr) => {
-                self.ram[(*addr + self.x as u16) as usize]
+                Ok(self.read(CPU::absolute_indexed(*addr, self.x)))
             }
             AddrMode::AbsY(addr) => {
-                self.ram[(*addr + self.y as u16) as usize]
+                Ok(self.read(CPU::absolute_indexed(*addr, self.y)))
             }
             AddrMode::Imm(value) => {
-                *value
+                Ok(*value)
             }
             AddrMode::Impl => {
-                panic!("Calling get_operand() for implied addressing mode does not make sense.")
+                Err("Calling get_operand() for implied addressing mode does not make sense.".to_string())
             }
-            AddrMode::Ind(addr) => {
-                let indirect = self.ram[*addr as usize] as usize;
-                self.ram[indirect]
+            AddrMode::Ind(_) => {
+                // indirect addressing is only valid for JMP, which resolves its own target
+                Err(format!("Indirect addressing mode has no operand for {:?}", instruction.ins_type))
             }
             AddrMode::XInd(addr) => {
-                let indirect = self.ram[(*addr + self.x) as usize] as usize;
-                self.ram[indirect]
+                let indirect = self.read_u16_zp(addr.wrapping_add(self.x));
+                Ok(self.read(indirect))
             }
             AddrMode::IndY(addr) => {
-                let indirect = self.ram[*addr as usize] as usize;
-                self.ram[indirect + self.y as usize]
+                let indirect = self.read_u16_zp(*addr);
+                Ok(self.read(CPU::absolute_indexed(indirect, self.y)))
             }
             AddrMode::Rel(value) => {
-                *value as u8
+                Ok(*value as u8)
             }
             AddrMode::Zpg(addr) => {
-                self.ram[*addr as usize]
+                Ok(self.read(*addr as u16))
             }
             AddrMode::ZpgX(addr) => {
-                self.ram[(*addr + self.x) as usize]
+                Ok(self.read(CPU::zero_page_indexed(*addr, self.x)))
             }
             AddrMode::ZpgY(addr) => {
-                self.ram[(*addr + self.y) as usize]
+                Ok(self.read(CPU::zero_page_indexed(*addr, self.y)))
             }
+            AddrMode::ZpgInd(addr) => {
+                let indirect = self.read_u16_zp(*addr);
+                Ok(self.read(indirect))
+            }
+        }
+    }
+
+    // check the condition of a branch instruction against the status register
+    fn branch_taken(&self, instruction: &Instruction) -> bool {
+        match instruction.ins_type {
+            InstructionType::BCC => self.sr.get_bit(CARRY_BIT) == 0,
+            InstructionType::BCS => self.sr.get_bit(CARRY_BIT) == 1,
+            InstructionType::BEQ => self.sr.get_bit(ZERO_BIT) == 1,
+            InstructionType::BMI => self.sr.get_bit(NEGATIVE_BIT) == 1,
+            InstructionType::BNE => self.sr.get_bit(ZERO_BIT) == 0,
+            InstructionType::BPL => self.sr.get_bit(NEGATIVE_BIT) == 0,
+            InstructionType::BVC => self.sr.get_bit(OVERFLOW_BIT) == 0,
+            InstructionType::BVS => self.sr.get_bit(OVERFLOW_BIT) == 1,
+            InstructionType::BRA => true,
+            _ => false,
         }
     }
 
+    // check whether indexing or branching moves the access to a different page
+    fn crosses_page(&self, instruction: &Instruction) -> bool {
+        let base = match &instruction.addr_mode {
+            AddrMode::AbsX(addr) | AddrMode::AbsY(addr) => *addr,
+            AddrMode::IndY(addr) => self.peek_u16_zp(*addr),
+            AddrMode::Rel(_) => self.pc.wrapping_add(instruction.machine_code.len() as u16),
+            _ => return false,
+        };
+        match self.effective_address(instruction) {
+            Some(addr) => addr & 0xff00 != base & 0xff00,
+            None => false,
+        }
+    }
+
+    // restore status register from a byte pulled off the stack
+    // the break bit only exists in pushed copies and the unused bit is always set
+    fn set_sr_from_stack(&mut self, status: u8) {
+        self.sr = status;
+        self.sr.clear_bit(BREAK_BIT);
+        self.sr.set_bit(UNUSED_BIT);
+    }
+
     // set zero and negative flags based on value
     fn set_sr_nz(&mut self, value: u8) {
         self.sr.assign_bit(NEGATIVE_BIT, value.get_bit(7));
@@ -681,7 +1639,21 @@ impl fmt::Display for CPU {
 
 #[cfg(test)]
 mod test {
-    use crate::cpu::{BitOps, CPU, CARRY_BIT, OVERFLOW_BIT};
+    use crate::cpu::isa::{AddrMode, Instruction, InstructionType};
+    use crate::cpu::{AccessKind, AccessRecord, BitOps, CpuRegisters, InstructionInfo, Prng, StatusFlag, CPU, CARRY_BIT, INT_DISABLE_BIT,
+        IRQ_VECTOR, NEGATIVE_BIT, NMI_VECTOR, OVERFLOW_BIT, Variant, ZERO_BIT};
+    use std::io::Read;
+
+    // compare memory against expected (address, value) pairs, reporting all mismatches at once
+    fn assert_memory_eq(cpu: &CPU, expected: &[(u16, u8)]) {
+        let mismatches: Vec<String> = expected.iter()
+            .filter(|(addr, value)| cpu.ram[*addr as usize] != *value)
+            .map(|(addr, value)| {
+                format!("${:04x}: expected ${:02x}, got ${:02x}", addr, value, cpu.ram[*addr as usize])
+            })
+            .collect();
+        assert!(mismatches.is_empty(), "memory mismatch:\n  {}", mismatches.join("\n  "));
+    }
 
     #[test]
     fn get_bit() {
@@ -856,5 +1828,1295 @@ mod test {
         assert_eq!(cpu.sr.get_bit(OVERFLOW_BIT), 0);
         assert_eq!(cpu.a, 0x60);
         assert_eq!(cpu.sr.get_bit(CARRY_BIT), 1u8 - 1);
+
+        // SEC, LDA #$80, SBC #$01
+        // -128 - 1 = 127 (should set overflow, no borrow)
+        for _i in 0..3 {
+            cpu.tick().unwrap();
+        }
+        assert_eq!(cpu.sr.get_bit(OVERFLOW_BIT), 1);
+        assert_eq!(cpu.a, 0x7f);
+        assert_eq!(cpu.sr.get_bit(CARRY_BIT), 1);    // carry is 1 - borrow
+
+        // SEC, LDA #$00, SBC #$01
+        // 0 - 1 = -1 (should not set overflow, borrow)
+        for _i in 0..3 {
+            cpu.tick().unwrap();
+        }
+        assert_eq!(cpu.sr.get_bit(OVERFLOW_BIT), 0);
+        assert_eq!(cpu.a, 0xff);
+        assert_eq!(cpu.sr.get_bit(CARRY_BIT), 1u8 - 1);
+        assert_eq!(cpu.sr.get_bit(NEGATIVE_BIT), 1);
+    }
+
+    // run a single branch with a known offset and assert whether it was taken
+    fn assert_branches(opcode: u8, flag_setup: fn(&mut u8), should_branch: bool) {
+        let mut cpu = CPU::init();
+        cpu.ram[0x0600] = opcode;
+        cpu.ram[0x0601] = 0x10;
+        cpu.pc = 0x0600;
+        flag_setup(&mut cpu.sr);
+
+        cpu.tick().unwrap();
+        let expected = if should_branch { 0x0612 } else { 0x0602 };
+        assert_eq!(cpu.pc, expected, "branch ${:02x} with SR {:08b}", opcode, cpu.sr);
+    }
+
+    #[test]
+    fn branches() {
+        // BPL, BMI
+        assert_branches(0x10, |sr| sr.clear_bit(NEGATIVE_BIT), true);
+        assert_branches(0x10, |sr| sr.set_bit(NEGATIVE_BIT), false);
+        assert_branches(0x30, |sr| sr.set_bit(NEGATIVE_BIT), true);
+        assert_branches(0x30, |sr| sr.clear_bit(NEGATIVE_BIT), false);
+
+        // BVC, BVS
+        assert_branches(0x50, |sr| sr.clear_bit(OVERFLOW_BIT), true);
+        assert_branches(0x50, |sr| sr.set_bit(OVERFLOW_BIT), false);
+        assert_branches(0x70, |sr| sr.set_bit(OVERFLOW_BIT), true);
+        assert_branches(0x70, |sr| sr.clear_bit(OVERFLOW_BIT), false);
+
+        // BCC, BCS
+        assert_branches(0x90, |sr| sr.clear_bit(CARRY_BIT), true);
+        assert_branches(0x90, |sr| sr.set_bit(CARRY_BIT), false);
+        assert_branches(0xb0, |sr| sr.set_bit(CARRY_BIT), true);
+        assert_branches(0xb0, |sr| sr.clear_bit(CARRY_BIT), false);
+
+        // BNE, BEQ
+        assert_branches(0xd0, |sr| sr.clear_bit(ZERO_BIT), true);
+        assert_branches(0xd0, |sr| sr.set_bit(ZERO_BIT), false);
+        assert_branches(0xf0, |sr| sr.set_bit(ZERO_BIT), true);
+        assert_branches(0xf0, |sr| sr.clear_bit(ZERO_BIT), false);
+    }
+
+    #[test]
+    fn disassemble_current() {
+        let mut cpu = CPU::init();
+
+        // BEQ $+4
+        cpu.ram[0x0600] = 0xf0;
+        cpu.ram[0x0601] = 0x02;
+        cpu.pc = 0x0600;
+
+        let disassembly = cpu.disassemble_current().unwrap();
+        assert!(disassembly.starts_with("$0600: f0 02"));
+        assert!(disassembly.contains("BEQ"));
+        assert!(disassembly.ends_with("; $0604"));
+        assert_eq!(cpu.pc, 0x0600);
+    }
+
+    #[test]
+    fn access_log() {
+        let mut cpu = CPU::init();
+        cpu.load_hexdump("./hexdumps/tests/access_log_test.txt").unwrap();
+        cpu.pc = 0x0600;
+
+        // LDA #$42 (logging disabled)
+        cpu.tick().unwrap();
+        assert!(cpu.access_log().is_empty());
+
+        // STA $0200, LDA $0200
+        cpu.enable_access_log(4);
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+
+        // only the last four accesses are kept
+        let expected = vec![
+            AccessRecord { kind: AccessKind::Read, addr: 0x0605, value: 0xad, cycle: 6 },
+            AccessRecord { kind: AccessKind::Read, addr: 0x0606, value: 0x00, cycle: 6 },
+            AccessRecord { kind: AccessKind::Read, addr: 0x0607, value: 0x02, cycle: 6 },
+            AccessRecord { kind: AccessKind::Read, addr: 0x0200, value: 0x42, cycle: 6 },
+        ];
+        assert_eq!(cpu.access_log(), expected);
+        // a zero capacity log stays empty
+        cpu.enable_access_log(0);
+        cpu.pc = 0x0600;
+        cpu.tick().unwrap();
+        assert!(cpu.access_log().is_empty());
+    }
+
+    #[test]
+    fn cycles_for() {
+        // LDA #$01
+        let lda_imm = Instruction::from(&[0xa9, 0x01, 0x00]).unwrap();
+        assert_eq!(CPU::cycles_for(&lda_imm, false, false), 2);
+
+        // LDA $0200,X
+        let lda_abs_x = Instruction::from(&[0xbd, 0x00, 0x02]).unwrap();
+        assert_eq!(CPU::cycles_for(&lda_abs_x, false, false), 4);
+        assert_eq!(CPU::cycles_for(&lda_abs_x, true, false), 5);
+
+        // LDA ($20),Y
+        let lda_ind_y = Instruction::from(&[0xb1, 0x20, 0x00]).unwrap();
+        assert_eq!(CPU::cycles_for(&lda_ind_y, false, false), 5);
+        assert_eq!(CPU::cycles_for(&lda_ind_y, true, false), 6);
+
+        // INC $0200,X
+        let inc_abs_x = Instruction::from(&[0xfe, 0x00, 0x02]).unwrap();
+        assert_eq!(CPU::cycles_for(&inc_abs_x, false, false), 7);
+
+        // BNE $02
+        let bne = Instruction::from(&[0xd0, 0x02, 0x00]).unwrap();
+        assert_eq!(CPU::cycles_for(&bne, false, false), 2);
+        assert_eq!(CPU::cycles_for(&bne, true, false), 2);
+        assert_eq!(CPU::cycles_for(&bne, false, true), 3);
+        assert_eq!(CPU::cycles_for(&bne, true, true), 4);
+    }
+
+    #[test]
+    fn cycle_counting() {
+        let mut cpu = CPU::init();
+
+        // LDX #$01, LDA $02ff,X, BEQ $00
+        let program = [0xa2, 0x01, 0xbd, 0xff, 0x02, 0xf0, 0x00];
+        cpu.load_program(&program, 0x0600).unwrap();
+
+        cpu.tick().unwrap();
+        assert_eq!(cpu.cycles, 2);
+
+        // $02ff + 1 crosses into page $03
+        cpu.tick().unwrap();
+        assert_eq!(cpu.cycles, 2 + 5);
+
+        // A is zero, branch is taken even though the target is the next instruction
+        cpu.tick().unwrap();
+        assert_eq!(cpu.cycles, 2 + 5 + 3);
+    }
+
+    #[test]
+    fn prng_reads() {
+        let mut cpu = CPU::init();
+        cpu.attach_prng(Prng::new(0xace1));
+
+        // LDA $fe, LDX $fe
+        let program = [0xa5, 0xfe, 0xa6, 0xfe];
+        cpu.load_program(&program, 0x0600).unwrap();
+
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+        assert_eq!(cpu.a, 0xc4);
+        assert_eq!(cpu.x, 0x62);
+        assert_eq!(cpu.ram[0x00fe], 0x00);
+    }
+
+    #[test]
+    fn easy6502_io() {
+        // LDA $fe, BMI $02, LDX #$01, LDY $ff
+        let run = |seed: u16| {
+            let mut cpu = CPU::init();
+            cpu.load_hexdump("./hexdumps/tests/easy6502_io_test.txt").unwrap();
+            cpu.enable_easy6502_io(seed);
+            cpu.set_last_key(0x77);
+            cpu.pc = 0x0600;
+            while cpu.pc != 0x0608 {
+                cpu.tick().unwrap();
+            }
+            cpu
+        };
+
+        // first random byte $c4 is negative (should branch over LDX)
+        let cpu = run(0xace1);
+        assert_eq!(cpu.a, 0xc4);
+        assert_eq!(cpu.x, 0x00);
+        assert_eq!(cpu.y, 0x77);
+
+        // first random byte $68 is positive (should not branch)
+        let cpu = run(0x0001);
+        assert_eq!(cpu.a, 0x68);
+        assert_eq!(cpu.x, 0x01);
+        assert_eq!(cpu.y, 0x77);
+    }
+
+    #[test]
+    fn status_flags() {
+        let mut cpu = CPU::init();
+
+        // LDA #$01, ADC #$01
+        let program = [0xa9, 0x01, 0x69, 0x01];
+        cpu.load_program(&program, 0x0600).unwrap();
+
+        cpu.set_flag(StatusFlag::Carry, true);
+        assert!(cpu.get_flag(StatusFlag::Carry));
+        assert_eq!(cpu.sr.get_bit(CARRY_BIT), 1);
+
+        // 1 + 1 + carry
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+        assert_eq!(cpu.a, 0x03);
+        assert!(!cpu.get_flag(StatusFlag::Carry));
+
+        cpu.set_flag(StatusFlag::Decimal, true);
+        cpu.set_flag(StatusFlag::InterruptDisable, false);
+        assert!(cpu.get_flag(StatusFlag::Decimal));
+        assert!(!cpu.get_flag(StatusFlag::InterruptDisable));
+    }
+
+    #[test]
+    fn unused_status_bit() {
+        let mut cpu = CPU::init();
+        assert_eq!(cpu.sr & 0x20, 0x20);
+
+        // PLP with $00 on top of the stack
+        cpu.ram[0x0600] = 0x28;
+        cpu.ram[0x01ff] = 0x00;
+        cpu.sp = 0xfe;
+        cpu.pc = 0x0600;
+        cpu.tick().unwrap();
+        assert_eq!(cpu.sr, 0x20);
+
+        // PLP with $ff on top of the stack, break bit is not kept
+        cpu.ram[0x0601] = 0x28;
+        cpu.ram[0x01ff] = 0xff;
+        cpu.sp = 0xfe;
+        cpu.tick().unwrap();
+        assert_eq!(cpu.sr, 0xef);
+    }
+
+    #[test]
+    fn rti() {
+        let mut cpu = CPU::init();
+
+        // RTI with status $c3 and return address $1234 on the stack
+        cpu.ram[0x0600] = 0x40;
+        cpu.ram[0x01fd] = 0xc3;
+        cpu.ram[0x01fe] = 0x34;
+        cpu.ram[0x01ff] = 0x12;
+        cpu.sp = 0xfc;
+        cpu.pc = 0x0600;
+        cpu.tick().unwrap();
+
+        assert_eq!(cpu.pc, 0x1234);
+        assert_eq!(cpu.sr, 0xe3);
+        assert_eq!(cpu.sp, 0xff);
+    }
+
+    #[test]
+    fn zero_page_pointer_wrap() {
+        let mut cpu = CPU::init();
+        cpu.load_hexdump("./hexdumps/tests/zp_pointer_wrap_test.txt").unwrap();
+        cpu.pc = 0x0600;
+
+        // pointer split across $ff/$00 points to $1234, not $5634
+        cpu.ram[0x00ff] = 0x34;
+        cpu.ram[0x0000] = 0x12;
+        cpu.ram[0x0100] = 0x56;
+        cpu.ram[0x1234] = 0x99;
+        cpu.ram[0x1235] = 0x98;
+
+        // LDA ($ff),Y with Y = 0
+        cpu.tick().unwrap();
+        assert_eq!(cpu.a, 0x99);
+
+        // LDX #$01, LDA ($fe,X)
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+        assert_eq!(cpu.a, 0x99);
+
+        // LDY #$01, LDA #$42, STA ($ff),Y
+        for _i in 0..3 {
+            cpu.tick().unwrap();
+        }
+        assert_eq!(cpu.ram[0x1235], 0x42);
+        assert_eq!(cpu.ram[0x5635], 0x00);
+    }
+
+    #[test]
+    fn interrupt_priority() {
+        let mut cpu = CPU::init();
+        cpu.load_hexdump("./hexdumps/tests/interrupt_test.txt").unwrap();
+        cpu.sp = 0xff;
+        cpu.pc = 0x0600;
+
+        // NMI handler at $0700, IRQ handler at $0800
+        cpu.ram[NMI_VECTOR as usize] = 0x00;
+        cpu.ram[NMI_VECTOR as usize + 1] = 0x07;
+        cpu.ram[IRQ_VECTOR as usize] = 0x00;
+        cpu.ram[IRQ_VECTOR as usize + 1] = 0x08;
+
+        // CLI with both NMI and IRQ held
+        cpu.trigger_nmi();
+        cpu.irq_line = true;
+        cpu.tick().unwrap();
+
+        // NMI is serviced first, IRQ is then masked by the interrupt disable flag
+        assert_eq!(cpu.pc, 0x0700);
+        assert_eq!(cpu.cycles, 2 + 7);
+        assert_eq!(cpu.sp, 0xfc);
+        assert_memory_eq(&cpu, &[(0x01ff, 0x06), (0x01fe, 0x01), (0x01fd, 0x20)]);
+        assert_eq!(cpu.sr.get_bit(INT_DISABLE_BIT), 1);
+
+        // RTI restores the cleared interrupt disable flag and the IRQ gets serviced
+        cpu.tick().unwrap();
+        assert_eq!(cpu.pc, 0x0800);
+    }
+
+    #[test]
+    fn halt_on_self_jump() {
+        // JMP $0600
+        let program = [0x4c, 0x00, 0x06];
+
+        let mut cpu = CPU::init();
+        cpu.load_program(&program, 0x0600).unwrap();
+        cpu.tick().unwrap();
+        assert!(!cpu.halted);
+
+        let mut cpu = CPU::init();
+        cpu.load_program(&program, 0x0600).unwrap();
+        cpu.halt_on_self_jump = true;
+        cpu.tick().unwrap();
+        assert!(cpu.halted);
+        assert_eq!(cpu.pc, 0x0600);
+
+        // a pending NMI is serviced instead of halting in the idle loop
+        let mut cpu = CPU::init();
+        cpu.load_program(&program, 0x0600).unwrap();
+        cpu.ram[NMI_VECTOR as usize] = 0x00;
+        cpu.ram[NMI_VECTOR as usize + 1] = 0x07;
+        cpu.sp = 0xff;
+        cpu.halt_on_self_jump = true;
+        cpu.trigger_nmi();
+        cpu.tick().unwrap();
+        assert!(!cpu.halted);
+        assert_eq!(cpu.pc, 0x0700);
+    }
+
+    #[test]
+    fn interrupt_vectors() {
+        let mut cpu = CPU::init();
+        cpu.ram[0xfffa..=0xffff].copy_from_slice(&[0x00, 0x80, 0x34, 0x12, 0xcd, 0xab]);
+
+        assert_eq!(cpu.nmi_addr(), 0x8000);
+        assert_eq!(cpu.reset_addr(), 0x1234);
+        assert_eq!(cpu.irq_addr(), 0xabcd);
+
+        // the high byte of a word at $ffff comes from $0000
+        cpu.ram[0x0000] = 0x56;
+        assert_eq!(cpu.peek_u16(0xffff), 0x56ab);
+    }
+
+    #[test]
+    fn dex_dey() {
+        let mut cpu = CPU::init();
+        cpu.load_hexdump("./hexdumps/tests/dex_dey_test.txt").unwrap();
+        cpu.pc = 0x0600;
+
+        // LDX #$00, DEX (should wrap to $ff and set negative)
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+        assert_eq!(cpu.x, 0xff);
+        assert_eq!(cpu.sr.get_bit(NEGATIVE_BIT), 1);
+        assert_eq!(cpu.sr.get_bit(ZERO_BIT), 0);
+
+        // LDY #$01, DEY (should set zero)
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+        assert_eq!(cpu.y, 0x00);
+        assert_eq!(cpu.sr.get_bit(NEGATIVE_BIT), 0);
+        assert_eq!(cpu.sr.get_bit(ZERO_BIT), 1);
+    }
+
+    #[test]
+    fn with_state() {
+        let registers = CpuRegisters {
+            a: 0x42,
+            x: 0x01,
+            y: 0x02,
+            sp: 0xfd,
+            pc: 0x0600,
+            sr: 0x24,
+        };
+        let mut cpu = CPU::with_state(registers);
+        assert_eq!(cpu.registers(), registers);
+
+        // PHA
+        cpu.ram[0x0600] = 0x48;
+        cpu.tick().unwrap();
+        assert_eq!(cpu.ram[0x01fd], 0x42);
+        assert_eq!(cpu.sp, 0xfc);
+
+        // break bit cleared, unused bit set
+        let cpu = CPU::with_state(CpuRegisters { sr: 0x10, ..registers });
+        assert_eq!(cpu.sr, 0x20);
+    }
+
+    #[test]
+    fn state_equals() {
+        let run = || {
+            let mut cpu = CPU::init();
+            cpu.load_hexdump("./hexdumps/tests/adc_carry_test.txt").unwrap();
+            cpu.attach_prng(Prng::new(0xace1));
+            cpu.pc = 0x0600;
+            for _i in 0..15 {
+                cpu.tick().unwrap();
+            }
+            cpu
+        };
+
+        let mut first = run();
+        let second = run();
+        assert!(first.state_equals(&second));
+
+        // debugging aids are not part of the state
+        first.enable_access_log(16);
+        assert!(first.state_equals(&second));
+
+        first.ram[0x0200] = 0x01;
+        assert!(!first.state_equals(&second));
+    }
+
+    #[test]
+    fn tick_cycle() {
+        let mut cpu = CPU::init();
+
+        // LDA #$01 (2 cycles), JMP $0600 (3 cycles)
+        let program = [0xa9, 0x01, 0x4c, 0x00, 0x06];
+        cpu.load_program(&program, 0x0600).unwrap();
+
+        let completed = (0..5)
+            .map(|_| cpu.tick_cycle().unwrap())
+            .collect::<Vec<bool>>();
+        assert_eq!(completed, vec![false, true, false, false, true]);
+        assert_eq!(cpu.a, 0x01);
+        assert_eq!(cpu.pc, 0x0600);
+        assert_eq!(cpu.cycles, 5);
+    }
+
+    #[test]
+    fn tick_cycle_steps() {
+        let mut cpu = CPU::init();
+        cpu.ram[0x0010] = 0x42;
+        cpu.enable_access_log(16);
+
+        // LDA $10 (3 cycles), STA $0200 (4 cycles)
+        let program = [0xa5, 0x10, 0x8d, 0x00, 0x02];
+        cpu.load_program(&program, 0x0600).unwrap();
+
+        // PC follows the fetched bytes, the load happens on the last cycle
+        assert!(!cpu.tick_cycle().unwrap());
+        assert_eq!((cpu.pc, cpu.a), (0x0601, 0x00));
+        assert!(!cpu.tick_cycle().unwrap());
+        assert_eq!((cpu.pc, cpu.a), (0x0602, 0x00));
+        assert!(cpu.tick_cycle().unwrap());
+        assert_eq!((cpu.pc, cpu.a), (0x0602, 0x42));
+
+        // the store writes on its fourth cycle only
+        for _i in 0..3 {
+            assert!(!cpu.tick_cycle().unwrap());
+            assert_eq!(cpu.ram[0x0200], 0x00);
+        }
+        assert!(cpu.tick_cycle().unwrap());
+        assert_eq!(cpu.ram[0x0200], 0x42);
+        assert_eq!(cpu.pc, 0x0605);
+        assert_eq!(cpu.cycles, 7);
+
+        // every access happens on its own cycle
+        let cycles = cpu.access_log()
+            .iter()
+            .map(|record| (record.kind, record.addr, record.cycle))
+            .collect::<Vec<(AccessKind, u16, u64)>>();
+        assert_eq!(cycles, vec![
+            (AccessKind::Read, 0x0600, 0),
+            (AccessKind::Read, 0x0601, 1),
+            (AccessKind::Read, 0x0010, 2),
+            (AccessKind::Read, 0x0602, 3),
+            (AccessKind::Read, 0x0603, 4),
+            (AccessKind::Read, 0x0604, 5),
+            (AccessKind::Write, 0x0200, 6),
+        ]);
+
+        // tick skips the wait of a whole instruction and finishes a stepped one
+        // JMP $0608 (3 cycles), LDA #$01 (2 cycles), LDX #$02 (2 cycles), NOP (2 cycles)
+        let program = [0x4c, 0x08, 0x06, 0xa9, 0x01, 0xa2, 0x02, 0xea];
+        cpu.load_bytes(0x0605, &program).unwrap();
+        assert!(!cpu.tick_cycle().unwrap());
+        cpu.tick().unwrap();
+        assert_eq!((cpu.pc, cpu.cycles, cpu.a), (0x060a, 12, 0x01));
+        assert!(!cpu.tick_cycle().unwrap());
+        assert_eq!((cpu.pc, cpu.cycles), (0x060b, 13));
+        cpu.tick().unwrap();
+        assert_eq!((cpu.pc, cpu.cycles, cpu.x), (0x060c, 14, 0x02));
+        cpu.advance(2).unwrap();
+        assert_eq!((cpu.pc, cpu.cycles), (0x060d, 16));
+    }
+
+    #[test]
+    fn instructions_iterator() {
+        let mut cpu = CPU::init();
+
+        // LDX #$03, DEX, BNE $fd, NOP
+        let program = [0xa2, 0x03, 0xca, 0xd0, 0xfd, 0xea];
+        cpu.load_program(&program, 0x0600).unwrap();
+
+        let pcs = cpu.instructions()
+            .take(8)
+            .collect::<Result<Vec<u16>, String>>()
+            .unwrap();
+        assert_eq!(pcs, vec![0x0600, 0x0602, 0x0603, 0x0602, 0x0603, 0x0602, 0x0603, 0x0605]);
+
+        // iteration ends after the first error ($02 is not a legal opcode)
+        cpu.ram[0x0606] = 0x02;
+        let steps = cpu.instructions().collect::<Vec<Result<u16, String>>>();
+        assert_eq!(steps.len(), 1);
+        assert!(steps[0].is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn execution_guard() {
+        let mut cpu = CPU::init();
+        cpu.set_execution_guard(Some(vec![0x0000..=0x1fff, 0x4020..=0xffff]));
+
+        // JMP $2000 into the PPU registers
+        let program = [0x4c, 0x00, 0x20];
+        cpu.load_program(&program, 0x0600).unwrap();
+        cpu.ram[0x2000] = 0xea;
+
+        cpu.tick().unwrap();
+        assert_eq!(cpu.pc, 0x2000);
+        assert!(cpu.tick().is_err());
+        assert_eq!(cpu.pc, 0x2000);
+
+        // without the guard the NOP executes
+        cpu.set_execution_guard(None);
+        cpu.tick().unwrap();
+        assert_eq!(cpu.pc, 0x2001);
+    }
+
+    #[test]
+    fn recorded_accesses() {
+        let mut cpu = CPU::init();
+        cpu.record_accesses = true;
+
+        // LDA #$42, STA $1234
+        let program = [0xa9, 0x42, 0x8d, 0x34, 0x12];
+        cpu.load_program(&program, 0x0600).unwrap();
+
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+        let expected = vec![
+            (AccessKind::Read, 0x0602, 0x8d),
+            (AccessKind::Read, 0x0603, 0x34),
+            (AccessKind::Read, 0x0604, 0x12),
+            (AccessKind::Write, 0x1234, 0x42),
+        ];
+        assert_eq!(cpu.recorded_accesses(), expected.as_slice());
+    }
+
+    #[test]
+    fn asl_bit_7() {
+        let mut cpu = CPU::init();
+
+        // ASL $80, LDA #$81, ASL A
+        let program = [0x06, 0x80, 0xa9, 0x81, 0x0a];
+        cpu.load_program(&program, 0x0600).unwrap();
+        cpu.ram[0x0080] = 0x80;
+
+        cpu.tick().unwrap();
+        assert_eq!(cpu.ram[0x0080], 0x00);
+        assert_eq!(cpu.sr.get_bit(CARRY_BIT), 1);
+        assert_eq!(cpu.sr.get_bit(ZERO_BIT), 1);
+        assert_eq!(cpu.sr.get_bit(NEGATIVE_BIT), 0);
+
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+        assert_eq!(cpu.a, 0x02);
+        assert_eq!(cpu.sr.get_bit(CARRY_BIT), 1);
+        assert_eq!(cpu.sr.get_bit(ZERO_BIT), 0);
+    }
+
+    #[test]
+    fn rol_bit_7() {
+        let mut cpu = CPU::init();
+
+        // SEC, ROL $80, CLC, ROL $80
+        let program = [0x38, 0x26, 0x80, 0x18, 0x26, 0x80];
+        cpu.load_program(&program, 0x0600).unwrap();
+        cpu.ram[0x0080] = 0x80;
+
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+        assert_eq!(cpu.ram[0x0080], 0x01);
+        assert_eq!(cpu.sr.get_bit(CARRY_BIT), 1);
+        assert_eq!(cpu.sr.get_bit(ZERO_BIT), 0);
+
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+        assert_eq!(cpu.ram[0x0080], 0x02);
+        assert_eq!(cpu.sr.get_bit(CARRY_BIT), 0);
+    }
+
+    #[test]
+    fn indirect_operand() {
+        let mut cpu = CPU::init();
+
+        // indirect addressing only exists for JMP, other instructions have no operand
+        let mut instruction = Instruction::from(&[0x6c, 0x34, 0x12]).unwrap();
+        instruction.ins_type = InstructionType::LDA;
+        assert!(cpu.execute(&instruction).is_err());
+    }
+
+    #[test]
+    fn vector_read_at_service_time() {
+        let mut cpu = CPU::init();
+        cpu.load_hexdump("./hexdumps/tests/interrupt_test.txt").unwrap();
+        cpu.ram[0x0601] = 0xea;
+        cpu.ram[0x0800] = 0x40;
+        cpu.sp = 0xff;
+        cpu.pc = 0x0600;
+        cpu.record_accesses = true;
+
+        // NMI handler at $0700
+        cpu.ram[NMI_VECTOR as usize] = 0x00;
+        cpu.ram[NMI_VECTOR as usize + 1] = 0x07;
+        cpu.trigger_nmi();
+        cpu.tick().unwrap();
+        assert_eq!(cpu.pc, 0x0700);
+
+        // the vector is fetched through the bus when the NMI is serviced
+        let accesses = cpu.recorded_accesses();
+        assert_eq!(accesses[accesses.len() - 2], (AccessKind::Read, NMI_VECTOR, 0x00));
+        assert_eq!(accesses[accesses.len() - 1], (AccessKind::Read, NMI_VECTOR + 1, 0x07));
+
+        // RTI, then a NOP with the vector pointing at $0800
+        cpu.tick().unwrap();
+        cpu.ram[NMI_VECTOR as usize + 1] = 0x08;
+        cpu.trigger_nmi();
+        cpu.tick().unwrap();
+        assert_eq!(cpu.pc, 0x0800);
+    }
+
+    #[test]
+    fn fetch_wraps_at_top_of_memory() {
+        let mut cpu = CPU::init();
+
+        // LDA $1234 with the opcode at $ffff and the operand at $0000-$0001
+        cpu.ram[0xffff] = 0xad;
+        cpu.ram[0x0000] = 0x34;
+        cpu.ram[0x0001] = 0x12;
+        cpu.ram[0x1234] = 0x42;
+        cpu.pc = 0xffff;
+
+        assert_eq!(cpu.disassemble_current().unwrap(), "$ffff: ad 34 12    LDA $1234     ; $1234");
+        cpu.tick().unwrap();
+        assert_eq!(cpu.a, 0x42);
+        assert_eq!(cpu.pc, 0x0002);
+    }
+
+    #[test]
+    fn trace_filter() {
+        let mut cpu = CPU::init();
+        cpu.capture_trace();
+        cpu.set_trace_filter(Some(0x0602..=0x0603));
+
+        // LDA #$01, LDX #$02, LDY #$03
+        let program = [0xa9, 0x01, 0xa2, 0x02, 0xa0, 0x03];
+        cpu.load_program(&program, 0x0600).unwrap();
+
+        for _i in 0..3 {
+            cpu.tick().unwrap();
+        }
+        assert_eq!(cpu.captured_trace().len(), 1);
+        assert!(cpu.captured_trace()[0].starts_with("$0602: a2 02"));
+
+        // without a filter every instruction is traced
+        cpu.set_trace_filter(None);
+        cpu.pc = 0x0600;
+        for _i in 0..3 {
+            cpu.tick().unwrap();
+        }
+        assert_eq!(cpu.captured_trace().len(), 4);
+    }
+
+    #[test]
+    fn store_cycles() {
+        // stores always spend the indexing cycle, page crossing does not matter
+        for y in [0x00, 0xff].iter() {
+            let mut cpu = CPU::init();
+
+            // STA ($20),Y, STA $2000,Y with ($20) pointing at $2080
+            let program = [0x91, 0x20, 0x99, 0x80, 0x20];
+            cpu.load_program(&program, 0x0600).unwrap();
+            cpu.ram[0x0020] = 0x80;
+            cpu.ram[0x0021] = 0x20;
+            cpu.a = 0x42;
+            cpu.y = *y;
+
+            cpu.tick().unwrap();
+            assert_eq!(cpu.cycles, 6);
+            assert_eq!(cpu.ram[0x2080 + *y as usize], 0x42);
+
+            cpu.tick().unwrap();
+            assert_eq!(cpu.cycles, 6 + 5);
+        }
+    }
+
+    #[test]
+    fn fetch_u16() {
+        let mut cpu = CPU::init();
+        cpu.ram[0xffff] = 0x34;
+        cpu.ram[0x0000] = 0x12;
+        cpu.ram[0x0001] = 0x56;
+
+        // operand of an opcode at $fffe is $ffff/$0000, at $ffff it is $0000/$0001
+        assert_eq!(cpu.fetch_u16(0xfffe), 0x1234);
+        assert_eq!(cpu.fetch_u16(0xffff), 0x5612);
+        assert_eq!(cpu.fetch_u16(0x0000), 0x0056);
+    }
+
+    #[test]
+    fn memory_reader() {
+        let mut cpu = CPU::init();
+        for i in 0..16 {
+            cpu.ram[0x0300 + i] = i as u8 * 3;
+        }
+
+        let mut buffer = [0; 16];
+        cpu.memory_reader(0x0300).read_exact(&mut buffer).unwrap();
+        for (i, byte) in buffer.iter().enumerate() {
+            assert_eq!(*byte, i as u8 * 3);
+        }
+
+        // reading stops at the top of memory
+        let mut buffer = [0; 4];
+        assert_eq!(cpu.memory_reader(0xfffe).read(&mut buffer).unwrap(), 2);
+        assert!(cpu.memory_reader(0xfffe).read_exact(&mut buffer).is_err());
+    }
+
+    #[test]
+    fn illegal_addressing_mode() {
+        let mut cpu = CPU::init();
+
+        // the decoder never produces ASL #imm, execute reports it instead of panicking
+        let mut instruction = Instruction::from(&[0x0a, 0x00, 0x00]).unwrap();
+        instruction.addr_mode = AddrMode::Imm(0x01);
+        assert!(cpu.execute(&instruction).is_err());
+
+        // STX $1234,X is not a valid store either
+        let mut instruction = Instruction::from(&[0x8e, 0x34, 0x12]).unwrap();
+        instruction.addr_mode = AddrMode::AbsX(0x1234);
+        assert!(cpu.execute(&instruction).is_err());
+    }
+
+    #[test]
+    fn unimplemented_instruction() {
+        // JMP ($1234) and PHP are legal but not emulated yet, tick reports them
+        for program in [[0x6c, 0x34, 0x12], [0x08, 0x00, 0x00]].iter() {
+            let mut cpu = CPU::init();
+            cpu.load_program(program, 0x0600).unwrap();
+            assert!(cpu.tick().is_err());
+            assert_eq!(cpu.pc, 0x0600);
+        }
+    }
+
+    #[test]
+    fn dump_state() {
+        let mut cpu = CPU::init();
+        cpu.a = 0x01;
+        cpu.x = 0x02;
+        cpu.y = 0x03;
+        cpu.sp = 0xfd;
+        cpu.ram[0x01fe] = 0x34;
+        cpu.ram[0x01ff] = 0x12;
+        cpu.sr.set_bit(CARRY_BIT);
+        cpu.sr.set_bit(NEGATIVE_BIT);
+
+        // LDA #$42
+        cpu.ram[0x0600] = 0xa9;
+        cpu.ram[0x0601] = 0x42;
+        cpu.pc = 0x0600;
+
+        let dump = cpu.dump_state();
+        assert!(dump.contains("A:$01 X:$02 Y:$03 SP:$fd PC:$0600"));
+        assert!(dump.contains("SR:$a5 Nv-bdIzC"));
+        assert!(dump.contains("Stack: 34 12"));
+        assert!(dump.contains("$0600: a9 42"));
+    }
+
+    #[test]
+    fn rotate_memory() {
+        let mut cpu = CPU::init();
+
+        // SEC, ROR $80, SEC, LDX #$01, ROL $80,X
+        let program = [0x38, 0x66, 0x80, 0x38, 0xa2, 0x01, 0x36, 0x80];
+        cpu.load_program(&program, 0x0600).unwrap();
+        cpu.ram[0x0080] = 0x02;
+        cpu.ram[0x0081] = 0x81;
+
+        // carry enters bit 7, bit 0 becomes the new carry
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+        assert_eq!(cpu.ram[0x0080], 0x81);
+        assert_eq!(cpu.sr.get_bit(CARRY_BIT), 0);
+        assert_eq!(cpu.sr.get_bit(NEGATIVE_BIT), 1);
+        assert_eq!(cpu.cycles, 2 + 5);
+
+        // carry enters bit 0, bit 7 becomes the new carry
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+        assert_eq!(cpu.ram[0x0081], 0x03);
+        assert_eq!(cpu.sr.get_bit(CARRY_BIT), 1);
+        assert_eq!(cpu.sr.get_bit(NEGATIVE_BIT), 0);
+        assert_eq!(cpu.sr.get_bit(ZERO_BIT), 0);
+        assert_eq!(cpu.cycles, 2 + 5 + 2 + 2 + 6);
+
+        // ROR A shifting out the last bit
+        let mut cpu = CPU::init();
+        cpu.ram[0x0600] = 0x6a;
+        cpu.a = 0x01;
+        cpu.pc = 0x0600;
+        cpu.tick().unwrap();
+        assert_eq!(cpu.a, 0x00);
+        assert_eq!(cpu.sr.get_bit(CARRY_BIT), 1);
+        assert_eq!(cpu.sr.get_bit(ZERO_BIT), 1);
+    }
+
+    #[test]
+    fn advance() {
+        // INC $10 (5 cycles), LDA $10 (3 cycles), STA $0200 (4 cycles), JMP $0600 (3 cycles)
+        let program = [0xe6, 0x10, 0xa5, 0x10, 0x8d, 0x00, 0x02, 0x4c, 0x00, 0x06];
+        let init = || {
+            let mut cpu = CPU::init();
+            cpu.load_program(&program, 0x0600).unwrap();
+            cpu
+        };
+
+        let mut whole = init();
+        whole.advance(15).unwrap();
+        assert_eq!(whole.ram[0x0200], 0x01);
+        assert_eq!(whole.pc, 0x0600);
+
+        // splitting in the middle of the store, before it writes
+        let mut split = init();
+        split.advance(10).unwrap();
+        assert_eq!(split.pc, 0x0606);
+        assert_eq!(split.a, 0x01);
+        assert_eq!(split.ram[0x0200], 0x00);
+        split.advance(5).unwrap();
+        assert!(split.state_equals(&whole));
+
+        // splitting in the middle of the load
+        let mut split = init();
+        split.advance(6).unwrap();
+        assert_eq!(split.a, 0x00);
+        split.advance(9).unwrap();
+        assert!(split.state_equals(&whole));
+    }
+
+    #[test]
+    fn wdc_65c02_variant() {
+        let mut cpu = CPU::init();
+
+        // LDA ($20), STA ($22), BRA $fa with ($20) = $0300 and ($22) = $0400
+        let program = [0xb2, 0x20, 0x92, 0x22, 0x80, 0xfa];
+        cpu.load_program(&program, 0x0600).unwrap();
+        cpu.ram[0x0020] = 0x00;
+        cpu.ram[0x0021] = 0x03;
+        cpu.ram[0x0022] = 0x00;
+        cpu.ram[0x0023] = 0x04;
+        cpu.ram[0x0300] = 0x42;
+
+        // the default NMOS decoder does not know the (zp) opcodes
+        assert!(cpu.tick().is_err());
+
+        cpu.variant = Variant::Wdc65C02;
+        assert_eq!(cpu.disassemble_current().unwrap(), "$0600: b2 20       LDA ($20)     ; $0300");
+        cpu.tick().unwrap();
+        assert_eq!(cpu.a, 0x42);
+        assert_eq!(cpu.cycles, 5);
+
+        cpu.tick().unwrap();
+        assert_eq!(cpu.ram[0x0400], 0x42);
+
+        // BRA is always taken
+        cpu.tick().unwrap();
+        assert_eq!(cpu.pc, 0x0600);
+        assert_eq!(cpu.cycles, 5 + 5 + 3);
+    }
+
+    #[test]
+    fn recent_instructions() {
+        let mut cpu = CPU::init();
+        cpu.enable_instruction_history(4);
+
+        // LDX #$03, DEX, BNE $fd, NOP
+        let program = [0xa2, 0x03, 0xca, 0xd0, 0xfd, 0xea];
+        cpu.load_program(&program, 0x0600).unwrap();
+
+        for _i in 0..8 {
+            cpu.tick().unwrap();
+        }
+        let expected = vec![(0x0603, 0xd0), (0x0602, 0xca), (0x0603, 0xd0), (0x0605, 0xea)];
+        assert_eq!(cpu.recent_instructions(), expected);
+        assert!(cpu.dump_state().contains("Recent: $0603:d0 $0602:ca $0603:d0 $0605:ea"));
+        // a zero capacity history stays empty
+        cpu.enable_instruction_history(0);
+        cpu.pc = 0x0600;
+        cpu.tick().unwrap();
+        assert!(cpu.recent_instructions().is_empty());
+    }
+
+    #[test]
+    fn stack_wrap() {
+        let mut cpu = CPU::init();
+        cpu.ram[0x0600] = 0x48; // PHA
+        cpu.ram[0x0601] = 0x28; // PLP
+        cpu.sp = 0xff;
+
+        // 260 pushes wrap around the stack page and overwrite the oldest slots
+        let value = |i: usize| (i % 251) as u8;
+        for i in 0..260 {
+            cpu.a = value(i);
+            cpu.pc = 0x0600;
+            cpu.tick().unwrap();
+        }
+        assert_eq!(cpu.sp, 0xfb);
+        assert_eq!(cpu.ram[0x01ff], value(256));
+        assert_eq!(cpu.ram[0x01fc], value(259));
+        assert_eq!(cpu.ram[0x01fb], value(4));
+        assert_eq!(cpu.ram[0x0100], value(255));
+
+        // nothing outside the stack page was touched
+        assert_eq!(cpu.ram[0x00ff], 0x00);
+        assert_eq!(cpu.ram[0x0200], 0x00);
+
+        // pulls wrap from $01ff back to $0100
+        cpu.sp = 0xff;
+        cpu.pc = 0x0601;
+        cpu.tick().unwrap();
+        assert_eq!(cpu.sp, 0x00);
+        assert_eq!(cpu.sr, value(255) | 0x20);
+    }
+
+    #[test]
+    fn load_bytes() {
+        let mut cpu = CPU::init();
+        cpu.enable_access_log(16);
+
+        let block: Vec<u8> = (0..0x4000).map(|i| (i * 7) as u8).collect();
+        cpu.load_bytes(0x8000, &block).unwrap();
+        assert_eq!(&cpu.ram[0x8000..0xc000], block.as_slice());
+        assert_eq!(cpu.ram[0x7fff], 0x00);
+        assert_eq!(cpu.ram[0xc000], 0x00);
+
+        // bulk loads do not go through the bus
+        assert!(cpu.access_log().is_empty());
+
+        // a block running past $ffff is rejected without writing anything
+        assert!(cpu.load_bytes(0xffff, &[0x01, 0x02]).is_err());
+        assert_eq!(cpu.ram[0xffff], 0x00);
+    }
+
+    #[test]
+    fn control_flow_cycles() {
+        let mut cpu = CPU::init();
+        cpu.sr.clear_bit(INT_DISABLE_BIT);
+        cpu.sp = 0xff;
+
+        // $0600: JSR $0700, BRK, $00, NOP; $0700: RTS; $0800: RTI
+        let program = [0x20, 0x00, 0x07, 0x00, 0x00, 0xea];
+        cpu.load_program(&program, 0x0600).unwrap();
+        cpu.ram[0x0700] = 0x60;
+        cpu.ram[0x0800] = 0x40;
+        cpu.ram[IRQ_VECTOR as usize] = 0x00;
+        cpu.ram[IRQ_VECTOR as usize + 1] = 0x08;
+        cpu.ram[NMI_VECTOR as usize] = 0x00;
+        cpu.ram[NMI_VECTOR as usize + 1] = 0x08;
+
+        cpu.tick().unwrap();
+        assert_eq!((cpu.pc, cpu.cycles), (0x0700, 6));
+        cpu.tick().unwrap();
+        assert_eq!((cpu.pc, cpu.cycles), (0x0603, 6 + 6));
+
+        // BRK pushes the address after its padding byte and the status with B set
+        cpu.tick().unwrap();
+        assert_eq!((cpu.pc, cpu.cycles), (0x0800, 12 + 7));
+        assert_memory_eq(&cpu, &[(0x01ff, 0x06), (0x01fe, 0x05), (0x01fd, 0x30)]);
+        assert_eq!(cpu.sr.get_bit(INT_DISABLE_BIT), 1);
+
+        cpu.tick().unwrap();
+        assert_eq!((cpu.pc, cpu.cycles), (0x0605, 19 + 6));
+        assert_eq!(cpu.sr.get_bit(INT_DISABLE_BIT), 0);
+
+        // NOP followed by servicing an NMI
+        cpu.trigger_nmi();
+        cpu.tick().unwrap();
+        assert_eq!((cpu.pc, cpu.cycles), (0x0800, 25 + 2 + 7));
+
+        // RTI followed by servicing an IRQ
+        cpu.irq_line = true;
+        cpu.tick().unwrap();
+        assert_eq!((cpu.pc, cpu.cycles), (0x0800, 34 + 6 + 7));
+    }
+
+    #[test]
+    fn step_over() {
+        let mut cpu = CPU::init();
+        cpu.sp = 0xff;
+
+        // $0600: JSR $0700, LDX #$05; $0700: LDA #$01, INY, RTS
+        let program = [0x20, 0x00, 0x07, 0xa2, 0x05];
+        cpu.load_program(&program, 0x0600).unwrap();
+        let subroutine = [0xa9, 0x01, 0xc8, 0x60];
+        cpu.load_bytes(0x0700, &subroutine).unwrap();
+
+        cpu.step_over().unwrap();
+        assert_eq!(cpu.pc, 0x0603);
+        assert_eq!(cpu.sp, 0xff);
+        assert_eq!((cpu.a, cpu.y), (0x01, 0x01));
+
+        // other instructions are single stepped
+        cpu.step_over().unwrap();
+        assert_eq!(cpu.pc, 0x0605);
+        assert_eq!(cpu.x, 0x05);
+    }
+
+    #[test]
+    fn step_out() {
+        let mut cpu = CPU::init();
+        cpu.sp = 0xff;
+
+        // $0600: JSR $0700, NOP; $0700: JSR $0800, INX, RTS; $0800: INY, RTS
+        let program = [0x20, 0x00, 0x07, 0xea];
+        cpu.load_program(&program, 0x0600).unwrap();
+        let outer = [0x20, 0x00, 0x08, 0xe8, 0x60];
+        cpu.load_bytes(0x0700, &outer).unwrap();
+        let inner = [0xc8, 0x60];
+        cpu.load_bytes(0x0800, &inner).unwrap();
+
+        // step into the outer subroutine, the nested call returns inside it
+        cpu.tick().unwrap();
+        cpu.step_out().unwrap();
+        assert_eq!(cpu.pc, 0x0603);
+        assert_eq!(cpu.sp, 0xff);
+        assert_eq!((cpu.x, cpu.y), (0x01, 0x01));
+
+        // $0700: PHA, PLP, RTS, stepping out after the push
+        let mut cpu = CPU::init();
+        cpu.sp = 0xff;
+        cpu.load_program(&[0x48, 0x28, 0x60], 0x0700).unwrap();
+        cpu.load_program(&[0x20, 0x00, 0x07, 0xea], 0x0600).unwrap();
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+        assert_eq!(cpu.sp, 0xfc);
+        cpu.step_out().unwrap();
+        assert_eq!(cpu.pc, 0x0603);
+        assert_eq!(cpu.sp, 0xff);
+    }
+
+    #[test]
+    fn pc_wrap() {
+        let mut cpu = CPU::init();
+        cpu.sp = 0xff;
+
+        // LDA $1234 straddling $ffff continues at $0001
+        cpu.ram[0xfffe] = 0xad;
+        cpu.ram[0xffff] = 0x34;
+        cpu.ram[0x0000] = 0x12;
+        cpu.ram[0x1234] = 0x42;
+        cpu.pc = 0xfffe;
+        cpu.tick().unwrap();
+        assert_eq!(cpu.pc, 0x0001);
+        assert_eq!(cpu.a, 0x42);
+
+        // BNE $04 at $fffe branches relative to $0000
+        cpu.ram[0xfffe] = 0xd0;
+        cpu.ram[0xffff] = 0x04;
+        cpu.pc = 0xfffe;
+        cpu.tick().unwrap();
+        assert_eq!(cpu.pc, 0x0004);
+
+        // BNE $fc at $0000 branches back over the top of memory
+        cpu.ram[0x0000] = 0xd0;
+        cpu.ram[0x0001] = 0xfc;
+        cpu.pc = 0x0000;
+        cpu.tick().unwrap();
+        assert_eq!(cpu.pc, 0xfffe);
+
+        // JSR at $fffd pushes $ffff and RTS returns to $0000
+        cpu.ram[0xfffd] = 0x20;
+        cpu.ram[0xfffe] = 0x00;
+        cpu.ram[0xffff] = 0x07;
+        cpu.ram[0x0700] = 0x60;
+        cpu.pc = 0xfffd;
+        cpu.tick().unwrap();
+        assert_eq!(cpu.pc, 0x0700);
+        assert_eq!((cpu.ram[0x01ff], cpu.ram[0x01fe]), (0xff, 0xff));
+        cpu.tick().unwrap();
+        assert_eq!(cpu.pc, 0x0000);
+    }
+
+    #[test]
+    fn load_program() {
+        let mut cpu = CPU::init();
+
+        // LDA #$ea, STA $8005, BRK patched to NOP by the store
+        let program = [0xa9, 0xea, 0x8d, 0x05, 0x80, 0x00];
+        cpu.load_program(&program, 0x8000).unwrap();
+        assert_eq!(cpu.pc, 0x8000);
+
+        for _i in 0..3 {
+            cpu.tick().unwrap();
+        }
+        assert_eq!(cpu.ram[0x8005], 0xea);
+        assert_eq!(cpu.pc, 0x8006);
+    }
+
+    #[test]
+    fn inspect_current() {
+        let mut cpu = CPU::init();
+
+        // LDA $0200
+        cpu.ram[0x0600] = 0xad;
+        cpu.ram[0x0601] = 0x00;
+        cpu.ram[0x0602] = 0x02;
+        cpu.ram[0x0200] = 0x42;
+        cpu.pc = 0x0600;
+        cpu.enable_access_log(4);
+
+        let info = cpu.inspect_current().unwrap();
+        assert_eq!(info, InstructionInfo {
+            mnemonic: "LDA",
+            addr_mode: AddrMode::Abs(0x0200),
+            operand_bytes: vec![0x00, 0x02],
+            effective_address: Some(0x0200),
+            value: Some(0x42),
+            cycles: 4,
+        });
+
+        // inspecting does not touch the bus or advance the CPU
+        assert!(cpu.access_log().is_empty());
+        assert_eq!(cpu.pc, 0x0600);
+    }
+
+    #[test]
+    fn with_status() {
+        // IRQ handler at $0700, NOP at $0600
+        let setup = |cpu: &mut CPU| {
+            cpu.ram[0x0600] = 0xea;
+            cpu.ram[IRQ_VECTOR as usize] = 0x00;
+            cpu.ram[IRQ_VECTOR as usize + 1] = 0x07;
+            cpu.sp = 0xff;
+            cpu.pc = 0x0600;
+            cpu.irq_line = true;
+        };
+
+        // power-on state masks the IRQ
+        let mut cpu = CPU::init();
+        setup(&mut cpu);
+        cpu.tick().unwrap();
+        assert_eq!(cpu.pc, 0x0601);
+
+        let mut cpu = CPU::with_status(0x00);
+        assert_eq!(cpu.sr, 0x20);
+        setup(&mut cpu);
+        cpu.tick().unwrap();
+        assert_eq!(cpu.pc, 0x0700);
+    }
+
+    #[test]
+    #[should_panic(expected = "$0201: expected $02, got $00")]
+    fn assert_memory_eq_mismatch() {
+        let mut cpu = CPU::init();
+        cpu.ram[0x0200] = 0x01;
+        assert_memory_eq(&cpu, &[(0x0200, 0x01), (0x0201, 0x02)]);
+    }
+
+    #[test]
+    fn compare_flags() {
+        // (register, operand, C, Z, N) where N is bit 7 of register - operand
+        let cases = [
+            (0x40, 0x80, 0, 0, 1),
+            (0x80, 0x40, 1, 0, 0),
+            (0x42, 0x42, 1, 1, 0),
+            (0x00, 0x00, 1, 1, 0),
+            (0x00, 0x01, 0, 0, 1),
+            (0xff, 0x00, 1, 0, 1),
+            (0x01, 0xff, 0, 0, 0),
+        ];
+
+        // CMP #imm, CPX #imm, CPY #imm
+        for opcode in [0xc9, 0xe0, 0xc0].iter() {
+            for (register, operand, carry, zero, negative) in cases.iter() {
+                // the result must not depend on the carry going in
+                for carry_in in 0..2 {
+                    let mut cpu = CPU::init();
+                    cpu.ram[0x0600] = *opcode;
+                    cpu.ram[0x0601] = *operand;
+                    cpu.pc = 0x0600;
+                    cpu.sr.assign_bit(CARRY_BIT, carry_in);
+                    match opcode {
+                        0xc9 => cpu.a = *register,
+                        0xe0 => cpu.x = *register,
+                        _ => cpu.y = *register,
+                    }
+
+                    cpu.tick().unwrap();
+                    let flags = (
+                        cpu.sr.get_bit(CARRY_BIT),
+                        cpu.sr.get_bit(ZERO_BIT),
+                        cpu.sr.get_bit(NEGATIVE_BIT),
+                    );
+                    assert_eq!(flags, (*carry, *zero, *negative),
+                        "${:02x} with register ${:02x} and operand ${:02x}", opcode, register, operand);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn break_on_brk() {
+        let mut cpu = CPU::init();
+        cpu.break_on_brk = true;
+
+        // LDA #$01, BRK, NOP
+        let program = [0xa9, 0x01, 0x00, 0xea];
+        cpu.load_program(&program, 0x0600).unwrap();
+
+        let pcs = cpu.instructions()
+            .collect::<Result<Vec<u16>, String>>()
+            .unwrap();
+        assert_eq!(pcs, vec![0x0600, 0x0602]);
+        assert!(cpu.halted);
+        assert_eq!(cpu.pc, 0x0602);
+        assert_eq!(cpu.cycles, 2);
+        assert_eq!(cpu.sp, 0x00);
+
+        // clock driven execution stops at the BRK as well
+        let mut cpu = CPU::init();
+        cpu.break_on_brk = true;
+        cpu.load_program(&program, 0x0600).unwrap();
+        cpu.advance(10).unwrap();
+        assert!(cpu.halted);
+        assert_eq!(cpu.pc, 0x0602);
+        assert_eq!(cpu.cycles, 2);
+        assert!(!cpu.tick_cycle().unwrap());
+        assert_eq!(cpu.cycles, 2);
+    }
+
+    #[test]
+    fn indexed_wrap() {
+        let mut cpu = CPU::init();
+        cpu.ram[0x0010] = 0x42;
+        cpu.ram[0x000f] = 0x24;
+        cpu.x = 0x20;
+        cpu.y = 0x1f;
+
+        // LDA $f0,X, STA $f1,X, LDA $fff0,Y
+        let program = [0xb5, 0xf0, 0x95, 0xf1, 0xb9, 0xf0, 0xff];
+        cpu.load_program(&program, 0x0600).unwrap();
+
+        // the debugger shows the address execution uses
+        assert!(cpu.disassemble_current().unwrap().ends_with("; $0010"));
+        cpu.tick().unwrap();
+        assert_eq!(cpu.a, 0x42);
+
+        assert!(cpu.disassemble_current().unwrap().ends_with("; $0011"));
+        cpu.tick().unwrap();
+        assert_eq!(cpu.ram[0x0011], 0x42);
+        assert_eq!(cpu.ram[0x0111], 0x00);
+
+        assert_eq!(cpu.inspect_current().unwrap().effective_address, Some(0x000f));
+        cpu.tick().unwrap();
+        assert_eq!(cpu.a, 0x24);
+    }
+}
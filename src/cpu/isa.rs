@@ -1,8 +1,9 @@
 /** Abstractions for the 6502 CPU instruction set **/
+use std::convert::TryFrom;
 use std::fmt;
 
 // instruction addressing mode with the associated argument (memory address / offset)
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub enum AddrMode {
     A,              // accumulator
     Abs(u16),       // absolute
@@ -17,16 +18,20 @@ pub enum AddrMode {
     Zpg(u8),        // zeropage
     ZpgX(u8),       // zeropage, X-indexed
     ZpgY(u8),       // zeropage, Y-indexed
+    ZpgInd(u8),     // zeropage, indirect (65C02 only)
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub enum InstructionType {
     ADC, AND, ASL, BCC, BCS, BEQ, BIT, BMI, BNE, BPL, BRK, BVC, BVS, CLC, CLD, CLI, CLV, CMP, CPX,
     CPY, DEC, DEX, DEY, EOR, INC, INX, INY, JMP, JSR, LDA, LDX, LDY, LSR, NOP, ORA, PHA, PHP, PLA,
     PLP, ROL, ROR, RTI, RTS, SBC, SEC, SED, SEI, STA, STX, STY, TAX, TAY, TSX, TXA, TXS, TYA,
+
+    // 65C02 only
+    BRA,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub struct InstructionName {
     pub mnemonic: &'static str,
     pub description: &'static str,
@@ -66,7 +71,7 @@ impl InstructionName {
             0x10 => { InstructionName { mnemonic: "BPL", description: "Branch on Result Plus", }}
             0x00 => { InstructionName { mnemonic: "BRK", description: "Force Break", }}
             0x50 => { InstructionName { mnemonic: "BVC", description: "Branch on Overflow Clear", }}
-            0x70 => { InstructionName { mnemonic: "BVC", description: "Branch on Overflow Clear", }}
+            0x70 => { InstructionName { mnemonic: "BVS", description: "Branch on Overflow Set", }}
             0x18 => { InstructionName { mnemonic: "CLC", description: "Clear Carry Flag", }}
             0xD8 => { InstructionName { mnemonic: "CLD", description: "Clear Decimal Mode", }}
             0x58 => { InstructionName { mnemonic: "CLI", description: "Clear Interrupt Disable Bit", }}
@@ -89,8 +94,8 @@ impl InstructionName {
             0xD6 => { InstructionName { mnemonic: "DEC", description: "Decrement Memory by One", }}
             0xCE => { InstructionName { mnemonic: "DEC", description: "Decrement Memory by One", }}
             0xDE => { InstructionName { mnemonic: "DEC", description: "Decrement Memory by One", }}
-            0xCA => { InstructionName { mnemonic: "DEC", description: "Decrement Memory by One", }}
-            0x88 => { InstructionName { mnemonic: "DEC", description: "Decrement Memory by One", }}
+            0xCA => { InstructionName { mnemonic: "DEX", description: "Decrement Index X by One", }}
+            0x88 => { InstructionName { mnemonic: "DEY", description: "Decrement Index Y by One", }}
             0x49 => { InstructionName { mnemonic: "EOR", description: "Exclusive-OR Memory with Accumulator", }}
             0x45 => { InstructionName { mnemonic: "EOR", description: "Exclusive-OR Memory with Accumulator", }}
             0x55 => { InstructionName { mnemonic: "EOR", description: "Exclusive-OR Memory with Accumulator", }}
@@ -192,7 +197,7 @@ impl InstructionName {
 }
 
 // Instruction to be executed by the processor and related useful information
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub struct Instruction {
     pub machine_code: Vec<u8>,
     pub ins_type: InstructionType,
@@ -489,7 +494,7 @@ impl Instruction {
             0x70 => {
                 let arg = get_u8(bytes)?;
                 Ok(Instruction {
-                    ins_type: InstructionType::BVC,
+                    ins_type: InstructionType::BVS,
                     machine_code: bytes.to_vec().into_iter().take(2).collect(),
                     addr_mode: AddrMode::Rel(arg as i8),
                     name: InstructionName::from(0x70),
@@ -1542,6 +1547,52 @@ impl Instruction {
             _ => Err(format!("Decoding not implemented for opcode: ${:02x}", bytes[0]))
         }
     }
+
+    // decode single instruction for the 65C02, which adds the (zp) addressing
+    // mode and BRA on top of the NMOS instruction set
+    pub fn from_65c02(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.is_empty() {
+            return Err("No bytes to decode!".to_string());
+        }
+
+        match bytes[0] {
+            // (zp) forms follow the (zp),Y form of the same instruction
+            0x12 | 0x32 | 0x52 | 0x72 | 0x92 | 0xB2 | 0xD2 | 0xF2 => {
+                let arg = get_u8(bytes)?;
+                let indexed = Instruction::from(&[bytes[0] - 1, arg])?;
+                Ok(Instruction {
+                    ins_type: indexed.ins_type,
+                    machine_code: bytes.iter().take(2).cloned().collect(),
+                    addr_mode: AddrMode::ZpgInd(arg),
+                    name: indexed.name,
+                })
+            }
+            0x80 => {
+                let arg = get_u8(bytes)?;
+                Ok(Instruction {
+                    ins_type: InstructionType::BRA,
+                    machine_code: bytes.iter().take(2).cloned().collect(),
+                    addr_mode: AddrMode::Rel(arg as i8),
+                    name: InstructionName { mnemonic: "BRA", description: "Branch Always", },
+                })
+            }
+            _ => Instruction::from(bytes)
+        }
+    }
+
+    // machine code as uppercase hex bytes padded to 8 columns like nestest.log, e.g. "A9 01   "
+    pub fn machine_code_hex(&self) -> String {
+        let bytes: Vec<String> = self.machine_code.iter().map(|byte| format!("{:02X}", byte)).collect();
+        format!("{:<8}", bytes.join(" "))
+    }
+}
+impl TryFrom<&[u8]> for Instruction {
+    type Error = String;
+
+    // same as Instruction::from, usable through `.try_into()`
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Instruction::from(bytes)
+    }
 }
 impl fmt::Display for Instruction {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -1568,6 +1619,7 @@ impl fmt::Display for Instruction {
             AddrMode::Zpg(addr) => write!(f, "${:02x}{}", addr, " ".repeat(WIDTH_2-3)),
             AddrMode::ZpgX(addr) => write!(f, "${:02x},X{}", addr, " ".repeat(WIDTH_2-5)),
             AddrMode::ZpgY(addr) => write!(f, "${:02x},Y{}", addr, " ".repeat(WIDTH_2-5)),
+            AddrMode::ZpgInd(addr) => write!(f, "(${:02x}){}", addr, " ".repeat(WIDTH_2-5)),
         }
     }
 }
@@ -1597,7 +1649,9 @@ fn get_u16(bytes: &[u8]) -> Result<u16, &str> {
 
 #[cfg(test)]
 mod test {
-    use crate::cpu::isa::{get_u8, get_u8_at, get_u16};
+    use crate::cpu::isa::{get_u8, get_u8_at, get_u16, Instruction, InstructionName, InstructionType, AddrMode};
+    use std::collections::HashSet;
+    use std::convert::TryInto;
 
     #[test]
     fn get_u8_valid() {
@@ -1627,4 +1681,234 @@ mod test {
         let value = get_u16(&bytes).unwrap();
         assert_eq!(0xabcd, value);
     }
+
+    #[test]
+    fn try_into_instruction() -> Result<(), String> {
+        let bytes = vec![0xa9, 0x01, 0x00];
+        let ins: Instruction = bytes.as_slice().try_into()?;
+        assert!(matches!(ins.ins_type, InstructionType::LDA));
+        assert!(matches!(ins.addr_mode, AddrMode::Imm(0x01)));
+        assert_eq!(ins.machine_code, vec![0xa9, 0x01]);
+
+        let empty: Result<Instruction, String> = [].as_ref().try_into();
+        assert!(empty.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn decode_equality() -> Result<(), String> {
+        let expected = Instruction {
+            machine_code: vec![0xa9, 0x01],
+            ins_type: InstructionType::LDA,
+            addr_mode: AddrMode::Imm(0x01),
+            name: InstructionName::from(0xa9),
+        };
+        assert_eq!(Instruction::from(&[0xa9, 0x01, 0x00])?, expected);
+        assert_ne!(Instruction::from(&[0xa9, 0x02, 0x00])?, expected);
+
+        // identical decodes deduplicate
+        let mut decoded = HashSet::new();
+        decoded.insert(Instruction::from(&[0xa9, 0x01, 0x00])?);
+        decoded.insert(Instruction::from(&[0xa9, 0x01, 0xff])?);
+        decoded.insert(Instruction::from(&[0xa5, 0x01, 0x00])?);
+        assert_eq!(decoded.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn dex_dey_names() -> Result<(), String> {
+        let dex = Instruction::from(&[0xca])?;
+        assert_eq!(dex.ins_type, InstructionType::DEX);
+        assert_eq!(dex.name.mnemonic, "DEX");
+
+        let dey = Instruction::from(&[0x88])?;
+        assert_eq!(dey.ins_type, InstructionType::DEY);
+        assert_eq!(dey.name.mnemonic, "DEY");
+        Ok(())
+    }
+
+    #[test]
+    fn machine_code_hex() {
+        let lda = Instruction::from(&[0xa9, 0x01, 0x00]).unwrap();
+        assert_eq!(lda.machine_code_hex(), "A9 01   ");
+        let jmp = Instruction::from(&[0x4c, 0xf5, 0xc5]).unwrap();
+        assert_eq!(jmp.machine_code_hex(), "4C F5 C5");
+        let nop = Instruction::from(&[0xea, 0x00, 0x00]).unwrap();
+        assert_eq!(nop.machine_code_hex(), "EA      ");
+    }
+
+    #[test]
+    fn opcode_coverage() {
+        use crate::cpu::isa::AddrMode::*;
+        use crate::cpu::isa::InstructionType::*;
+
+        // every legal opcode with its operand bytes 34 12 decoded, from scripts/instructions.txt
+        let legal_opcodes = [
+            (0x69, ADC, Imm(0x34), 2),
+            (0x65, ADC, Zpg(0x34), 2),
+            (0x75, ADC, ZpgX(0x34), 2),
+            (0x6d, ADC, Abs(0x1234), 3),
+            (0x7d, ADC, AbsX(0x1234), 3),
+            (0x79, ADC, AbsY(0x1234), 3),
+            (0x61, ADC, XInd(0x34), 2),
+            (0x71, ADC, IndY(0x34), 2),
+            (0x29, AND, Imm(0x34), 2),
+            (0x25, AND, Zpg(0x34), 2),
+            (0x35, AND, ZpgX(0x34), 2),
+            (0x2d, AND, Abs(0x1234), 3),
+            (0x3d, AND, AbsX(0x1234), 3),
+            (0x39, AND, AbsY(0x1234), 3),
+            (0x21, AND, XInd(0x34), 2),
+            (0x31, AND, IndY(0x34), 2),
+            (0x0a, ASL, A, 1),
+            (0x06, ASL, Zpg(0x34), 2),
+            (0x16, ASL, ZpgX(0x34), 2),
+            (0x0e, ASL, Abs(0x1234), 3),
+            (0x1e, ASL, AbsX(0x1234), 3),
+            (0x90, BCC, Rel(0x34), 2),
+            (0xb0, BCS, Rel(0x34), 2),
+            (0xf0, BEQ, Rel(0x34), 2),
+            (0x24, BIT, Zpg(0x34), 2),
+            (0x2c, BIT, Abs(0x1234), 3),
+            (0x30, BMI, Rel(0x34), 2),
+            (0xd0, BNE, Rel(0x34), 2),
+            (0x10, BPL, Rel(0x34), 2),
+            (0x00, BRK, Impl, 1),
+            (0x50, BVC, Rel(0x34), 2),
+            (0x70, BVS, Rel(0x34), 2),
+            (0x18, CLC, Impl, 1),
+            (0xd8, CLD, Impl, 1),
+            (0x58, CLI, Impl, 1),
+            (0xb8, CLV, Impl, 1),
+            (0xc9, CMP, Imm(0x34), 2),
+            (0xc5, CMP, Zpg(0x34), 2),
+            (0xd5, CMP, ZpgX(0x34), 2),
+            (0xcd, CMP, Abs(0x1234), 3),
+            (0xdd, CMP, AbsX(0x1234), 3),
+            (0xd9, CMP, AbsY(0x1234), 3),
+            (0xc1, CMP, XInd(0x34), 2),
+            (0xd1, CMP, IndY(0x34), 2),
+            (0xe0, CPX, Imm(0x34), 2),
+            (0xe4, CPX, Zpg(0x34), 2),
+            (0xec, CPX, Abs(0x1234), 3),
+            (0xc0, CPY, Imm(0x34), 2),
+            (0xc4, CPY, Zpg(0x34), 2),
+            (0xcc, CPY, Abs(0x1234), 3),
+            (0xc6, DEC, Zpg(0x34), 2),
+            (0xd6, DEC, ZpgX(0x34), 2),
+            (0xce, DEC, Abs(0x1234), 3),
+            (0xde, DEC, AbsX(0x1234), 3),
+            (0xca, DEX, Impl, 1),
+            (0x88, DEY, Impl, 1),
+            (0x49, EOR, Imm(0x34), 2),
+            (0x45, EOR, Zpg(0x34), 2),
+            (0x55, EOR, ZpgX(0x34), 2),
+            (0x4d, EOR, Abs(0x1234), 3),
+            (0x5d, EOR, AbsX(0x1234), 3),
+            (0x59, EOR, AbsY(0x1234), 3),
+            (0x41, EOR, XInd(0x34), 2),
+            (0x51, EOR, IndY(0x34), 2),
+            (0xe6, INC, Zpg(0x34), 2),
+            (0xf6, INC, ZpgX(0x34), 2),
+            (0xee, INC, Abs(0x1234), 3),
+            (0xfe, INC, AbsX(0x1234), 3),
+            (0xe8, INX, Impl, 1),
+            (0xc8, INY, Impl, 1),
+            (0x4c, JMP, Abs(0x1234), 3),
+            (0x6c, JMP, Ind(0x1234), 3),
+            (0x20, JSR, Abs(0x1234), 3),
+            (0xa9, LDA, Imm(0x34), 2),
+            (0xa5, LDA, Zpg(0x34), 2),
+            (0xb5, LDA, ZpgX(0x34), 2),
+            (0xad, LDA, Abs(0x1234), 3),
+            (0xbd, LDA, AbsX(0x1234), 3),
+            (0xb9, LDA, AbsY(0x1234), 3),
+            (0xa1, LDA, XInd(0x34), 2),
+            (0xb1, LDA, IndY(0x34), 2),
+            (0xa2, LDX, Imm(0x34), 2),
+            (0xa6, LDX, Zpg(0x34), 2),
+            (0xb6, LDX, ZpgY(0x34), 2),
+            (0xae, LDX, Abs(0x1234), 3),
+            (0xbe, LDX, AbsY(0x1234), 3),
+            (0xa0, LDY, Imm(0x34), 2),
+            (0xa4, LDY, Zpg(0x34), 2),
+            (0xb4, LDY, ZpgX(0x34), 2),
+            (0xac, LDY, Abs(0x1234), 3),
+            (0xbc, LDY, AbsX(0x1234), 3),
+            (0x4a, LSR, A, 1),
+            (0x46, LSR, Zpg(0x34), 2),
+            (0x56, LSR, ZpgX(0x34), 2),
+            (0x4e, LSR, Abs(0x1234), 3),
+            (0x5e, LSR, AbsX(0x1234), 3),
+            (0xea, NOP, Impl, 1),
+            (0x09, ORA, Imm(0x34), 2),
+            (0x05, ORA, Zpg(0x34), 2),
+            (0x15, ORA, ZpgX(0x34), 2),
+            (0x0d, ORA, Abs(0x1234), 3),
+            (0x1d, ORA, AbsX(0x1234), 3),
+            (0x19, ORA, AbsY(0x1234), 3),
+            (0x01, ORA, XInd(0x34), 2),
+            (0x11, ORA, IndY(0x34), 2),
+            (0x48, PHA, Impl, 1),
+            (0x08, PHP, Impl, 1),
+            (0x68, PLA, Impl, 1),
+            (0x28, PLP, Impl, 1),
+            (0x2a, ROL, A, 1),
+            (0x26, ROL, Zpg(0x34), 2),
+            (0x36, ROL, ZpgX(0x34), 2),
+            (0x2e, ROL, Abs(0x1234), 3),
+            (0x3e, ROL, AbsX(0x1234), 3),
+            (0x6a, ROR, A, 1),
+            (0x66, ROR, Zpg(0x34), 2),
+            (0x76, ROR, ZpgX(0x34), 2),
+            (0x6e, ROR, Abs(0x1234), 3),
+            (0x7e, ROR, AbsX(0x1234), 3),
+            (0x40, RTI, Impl, 1),
+            (0x60, RTS, Impl, 1),
+            (0xe9, SBC, Imm(0x34), 2),
+            (0xe5, SBC, Zpg(0x34), 2),
+            (0xf5, SBC, ZpgX(0x34), 2),
+            (0xed, SBC, Abs(0x1234), 3),
+            (0xfd, SBC, AbsX(0x1234), 3),
+            (0xf9, SBC, AbsY(0x1234), 3),
+            (0xe1, SBC, XInd(0x34), 2),
+            (0xf1, SBC, IndY(0x34), 2),
+            (0x38, SEC, Impl, 1),
+            (0xf8, SED, Impl, 1),
+            (0x78, SEI, Impl, 1),
+            (0x85, STA, Zpg(0x34), 2),
+            (0x95, STA, ZpgX(0x34), 2),
+            (0x8d, STA, Abs(0x1234), 3),
+            (0x9d, STA, AbsX(0x1234), 3),
+            (0x99, STA, AbsY(0x1234), 3),
+            (0x81, STA, XInd(0x34), 2),
+            (0x91, STA, IndY(0x34), 2),
+            (0x86, STX, Zpg(0x34), 2),
+            (0x96, STX, ZpgY(0x34), 2),
+            (0x8e, STX, Abs(0x1234), 3),
+            (0x84, STY, Zpg(0x34), 2),
+            (0x94, STY, ZpgX(0x34), 2),
+            (0x8c, STY, Abs(0x1234), 3),
+            (0xaa, TAX, Impl, 1),
+            (0xa8, TAY, Impl, 1),
+            (0xba, TSX, Impl, 1),
+            (0x8a, TXA, Impl, 1),
+            (0x9a, TXS, Impl, 1),
+            (0x98, TYA, Impl, 1),
+        ];
+        assert_eq!(legal_opcodes.len(), 151);
+
+        for opcode in 0..=0xff {
+            let decoded = Instruction::from(&[opcode, 0x34, 0x12]);
+            match legal_opcodes.iter().find(|(legal, _, _, _)| *legal == opcode) {
+                Some((_, ins_type, addr_mode, len)) => {
+                    let instruction = decoded.unwrap();
+                    assert_eq!(&instruction.ins_type, ins_type, "type of ${:02x}", opcode);
+                    assert_eq!(&instruction.addr_mode, addr_mode, "mode of ${:02x}", opcode);
+                    assert_eq!(instruction.machine_code.len(), *len, "length of ${:02x}", opcode);
+                }
+                None => assert!(decoded.is_err(), "illegal opcode ${:02x} decoded", opcode),
+            }
+        }
+    }
 }
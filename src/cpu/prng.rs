@@ -0,0 +1,70 @@
+/** Deterministic pseudo random number generator mapped to a single address **/
+// easy6502 programs read random bytes from $fe
+pub const DEFAULT_PRNG_ADDR: u16 = 0x00fe;
+
+// 16-bit Galois LFSR with taps 16, 14, 13, 11 (maximal period of 65535)
+const LFSR_TAPS: u16 = 0xb400;
+
+// every read of the mapped address clocks the LFSR and returns its low byte
+#[derive(Debug, PartialEq)]
+pub struct Prng {
+    pub addr: u16,
+    state: u16,
+}
+impl Prng {
+    // generator mapped at the easy6502 random byte address
+    pub fn new(seed: u16) -> Self {
+        Prng::at(DEFAULT_PRNG_ADDR, seed)
+    }
+
+    // generator mapped at a custom address
+    pub fn at(addr: u16, seed: u16) -> Self {
+        // an all zero LFSR never leaves the zero state
+        let state = match seed {
+            0 => 1,
+            _ => seed,
+        };
+        Prng { addr, state }
+    }
+
+    // advance the generator by one byte worth of shifts
+    pub fn next_byte(&mut self) -> u8 {
+        for _i in 0..8 {
+            let lsb = self.state & 1;
+            self.state >>= 1;
+            if lsb == 1 {
+                self.state ^= LFSR_TAPS;
+            }
+        }
+        (self.state & 0xff) as u8
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::cpu::prng::{Prng, DEFAULT_PRNG_ADDR};
+
+    #[test]
+    fn default_address() {
+        let prng = Prng::new(0xace1);
+        assert_eq!(prng.addr, DEFAULT_PRNG_ADDR);
+        assert_eq!(Prng::at(0x4000, 0xace1).addr, 0x4000);
+    }
+
+    #[test]
+    fn lfsr_sequence() {
+        let mut prng = Prng::new(0xace1);
+        let expected: [u8; 8] = [0xc4, 0x62, 0x3b, 0x0d, 0xbb, 0x77, 0x1f, 0xbf];
+        for value in expected.iter() {
+            assert_eq!(*value, prng.next_byte());
+        }
+    }
+
+    #[test]
+    fn zero_seed() {
+        // zero seed must not lock the generator at zero
+        let mut prng = Prng::new(0);
+        assert!((0..16).any(|_| prng.next_byte() != 0));
+    }
+}
@@ -0,0 +1,135 @@
+/** Harness for the SingleStepTests ("Tom Harte") JSON CPU test vectors **/
+// each vector holds the initial and final state of one instruction together
+// with the bus accesses it performs, the loader reads them from
+// fixtures/single_step/<opcode>.json
+use crate::cpu::{AccessKind, CpuRegisters, BREAK_BIT, CPU};
+use serde_json::Value;
+use std::fs;
+
+const FIXTURES_DIR: &str = "./fixtures/single_step";
+
+// registers and the touched memory of the CPU before or after the instruction
+struct VectorState {
+    registers: CpuRegisters,
+    ram: Vec<(u16, u8)>,
+}
+
+struct TestVector {
+    name: String,
+    initial: VectorState,
+    fin: VectorState,
+    cycles: Vec<(AccessKind, u16, u8)>,
+}
+
+fn as_u16(value: &Value) -> Result<u16, String> {
+    value.as_u64()
+        .filter(|n| *n <= 0xffff)
+        .map(|n| n as u16)
+        .ok_or(format!("Expected a 16-bit number, got {}", value))
+}
+
+fn as_u8(value: &Value) -> Result<u8, String> {
+    value.as_u64()
+        .filter(|n| *n <= 0xff)
+        .map(|n| n as u8)
+        .ok_or(format!("Expected an 8-bit number, got {}", value))
+}
+
+fn as_array(value: &Value) -> Result<&Vec<Value>, String> {
+    value.as_array().ok_or(format!("Expected an array, got {}", value))
+}
+
+fn parse_state(value: &Value) -> Result<VectorState, String> {
+    let registers = CpuRegisters {
+        a: as_u8(&value["a"])?,
+        x: as_u8(&value["x"])?,
+        y: as_u8(&value["y"])?,
+        sp: as_u8(&value["s"])?,
+        pc: as_u16(&value["pc"])?,
+        // the break bit only exists on the stack, the CPU never holds it
+        sr: as_u8(&value["p"])? & !(1 << BREAK_BIT),
+    };
+    let mut ram = Vec::new();
+    for entry in as_array(&value["ram"])? {
+        let entry = as_array(entry)?;
+        if entry.len() != 2 {
+            return Err(format!("Invalid ram entry: {:?}", entry));
+        }
+        ram.push((as_u16(&entry[0])?, as_u8(&entry[1])?));
+    }
+    Ok(VectorState { registers, ram })
+}
+
+fn parse_vector(value: &Value) -> Result<TestVector, String> {
+    let mut cycles = Vec::new();
+    for cycle in as_array(&value["cycles"])? {
+        let cycle = as_array(cycle)?;
+        if cycle.len() != 3 {
+            return Err(format!("Invalid cycle entry: {:?}", cycle));
+        }
+        let kind = match cycle[2].as_str() {
+            Some("read") => AccessKind::Read,
+            Some("write") => AccessKind::Write,
+            _ => return Err(format!("Invalid access kind: {}", cycle[2])),
+        };
+        cycles.push((kind, as_u16(&cycle[0])?, as_u8(&cycle[1])?));
+    }
+    Ok(TestVector {
+        name: value["name"].as_str().unwrap_or("").to_string(),
+        initial: parse_state(&value["initial"])?,
+        fin: parse_state(&value["final"])?,
+        cycles,
+    })
+}
+
+// load all vectors of an opcode from the fixtures directory
+fn load_vectors(opcode: u8) -> Result<Vec<TestVector>, String> {
+    let path = format!("{}/{:02x}.json", FIXTURES_DIR, opcode);
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let json: Value = serde_json::from_str(&contents)
+        .map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+    as_array(&json)?.iter().map(parse_vector).collect()
+}
+
+// run one instruction of every vector and compare the end state to the vector
+fn run_vectors(opcode: u8) {
+    let vectors = load_vectors(opcode).unwrap();
+    assert!(!vectors.is_empty(), "No vectors for opcode {:02x}", opcode);
+
+    for vector in vectors {
+        let mut cpu = CPU::with_state(vector.initial.registers);
+        for (addr, value) in vector.initial.ram.iter() {
+            cpu.ram[*addr as usize] = *value;
+        }
+        cpu.record_accesses = true;
+
+        cpu.tick().unwrap();
+
+        assert_eq!(cpu.registers(), vector.fin.registers, "registers of \"{}\"", vector.name);
+        for (addr, value) in vector.fin.ram.iter() {
+            assert_eq!(cpu.ram[*addr as usize], *value, "${:04x} of \"{}\"", addr, vector.name);
+        }
+        assert_eq!(cpu.recorded_accesses(), vector.cycles.as_slice(), "accesses of \"{}\"", vector.name);
+        assert_eq!(cpu.cycles, vector.cycles.len() as u64, "cycles of \"{}\"", vector.name);
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::cpu::single_step::run_vectors;
+
+    #[test]
+    fn lda() {
+        run_vectors(0xa9);
+        run_vectors(0xa5);
+        run_vectors(0xad);
+    }
+
+    #[test]
+    fn sta() {
+        run_vectors(0x85);
+        run_vectors(0x8d);
+    }
+}
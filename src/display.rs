@@ -0,0 +1,70 @@
+/** easy6502 compatible 32x32 pixel display mapped to $0200-$05ff **/
+use crate::cpu::CPU;
+
+pub const DISPLAY_START: u16 = 0x0200;
+pub const DISPLAY_WIDTH: usize = 32;
+pub const DISPLAY_HEIGHT: usize = 32;
+
+// RGB colors of the 16 color indices, upper nibble of a pixel byte is ignored
+pub const PALETTE: [u32; 16] = [
+    0x000000, 0xffffff, 0x880000, 0xaaffee,     // black, white, red, cyan
+    0xcc44cc, 0x00cc55, 0x0000aa, 0xeeee77,     // purple, green, blue, yellow
+    0xdd8855, 0x664400, 0xff7777, 0x333333,     // orange, brown, light red, dark grey
+    0x777777, 0xaaff66, 0x0088ff, 0xbbbbbb,     // grey, light green, light blue, light grey
+];
+
+// view of the display memory, one byte per pixel in row-major order
+pub struct PixelDisplay<'a> {
+    memory: &'a [u8],
+}
+impl<'a> PixelDisplay<'a> {
+    pub fn of(cpu: &'a CPU) -> Self {
+        let start = DISPLAY_START as usize;
+        PixelDisplay {
+            memory: &cpu.ram[start..start + DISPLAY_WIDTH * DISPLAY_HEIGHT],
+        }
+    }
+
+    // color indices of all pixels
+    pub fn pixels(&self) -> &[u8] {
+        self.memory
+    }
+
+    // color index of the pixel at column x, row y
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        self.memory[y * DISPLAY_WIDTH + x] & 0x0f
+    }
+
+    // RGB color of the pixel at column x, row y
+    pub fn rgb(&self, x: usize, y: usize) -> u32 {
+        PALETTE[self.pixel(x, y) as usize]
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::cpu::CPU;
+    use crate::display::{PixelDisplay, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+    #[test]
+    fn pixel_coordinates() {
+        let mut cpu = CPU::init();
+        cpu.load_hexdump("./hexdumps/tests/display_test.txt").unwrap();
+        cpu.pc = 0x0600;
+
+        // LDA #$05, STA $0200, LDA #$02, STA $0221, LDA #$01, STA $05ff
+        for _i in 0..6 {
+            cpu.tick().unwrap();
+        }
+
+        let display = PixelDisplay::of(&cpu);
+        assert_eq!(display.pixels().len(), DISPLAY_WIDTH * DISPLAY_HEIGHT);
+        assert_eq!(display.pixel(0, 0), 0x05);
+        assert_eq!(display.pixel(1, 1), 0x02);
+        assert_eq!(display.pixel(31, 31), 0x01);
+        assert_eq!(display.pixel(1, 0), 0x00);
+        assert_eq!(display.rgb(0, 0), 0x00cc55);
+        assert_eq!(display.rgb(31, 31), 0xffffff);
+    }
+}
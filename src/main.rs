@@ -1,14 +1,15 @@
-mod cpu;
-mod util;
-use crate::cpu::CPU;
+use nes::cpu::CPU;
 
 fn main() {
     let mut cpu = CPU::init();
 
     cpu.load_hexdump("./hexdumps/tmp.txt").unwrap();
+    cpu.enable_easy6502_io(0xace1);
     cpu.pc = 0x0600;
+    cpu.halt_on_self_jump = true;
+    cpu.break_on_brk = true;
 
-    loop {
+    while !cpu.halted {
         cpu.tick().unwrap();
     }
 